@@ -9,6 +9,12 @@ export default class EmulatorBridge {
   private speed = 0;
   private maxFrameTime: number = 0;
   public running = $state(false);
+  private lockedUp = false;
+
+  /**
+   * Called with the address of the illegal opcode when the CPU locks up
+   */
+  public onLockup: ((address: number) => void) | undefined = undefined;
 
   initialize = (options: Options) => {
     this.proxy = spawn_event_loop();
@@ -62,9 +68,18 @@ export default class EmulatorBridge {
     this.lastFrameTime = currentTime;
 
     console.info(`Queried CPU to execute for ${timeToExecute} ms`);
-    this.proxy?.query({ RunCPU: { millis: this.speed * timeToExecute } }).then(() => {
+    this.proxy?.query({ RunCPU: { millis: this.speed * timeToExecute } }).then((lockupAddress) => {
       let executionTime = performance.now() - currentTime;
       console.info(`CPU took ${executionTime} ms to execute`);
+      // CPU resolves with the address of the illegal opcode it locked up on
+      if (typeof lockupAddress === "number") {
+        if (!this.lockedUp) {
+          this.lockedUp = true;
+          this.onLockup?.(lockupAddress);
+        }
+      } else {
+        this.lockedUp = false;
+      }
     });
     window.requestAnimationFrame(this.runEmulator);
   }
@@ -106,6 +121,111 @@ export default class EmulatorBridge {
     return this.proxy.query({ UpdateOptions: { options: toEmulatorOptions(options) } }) as Promise<void>;
   }
 
+  /**
+   * Sets the boot ROM that is run the next time a ROM is loaded, or removes it if null
+   */
+  setBootROM = async (bootROM: Uint8Array | null) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ SetBootROM: { boot_rom: bootROM } }) as Promise<void>;
+  }
+
+  /**
+   * Sets the hardware model (DMG0, DMG, MGB, SGB, SGB2 or CGB) that is emulated the next time a ROM is loaded.
+   * Auto picks CGB for cartridges that support color, SGB for cartridges that only support Super Game Boy
+   * and DMG for the rest
+   */
+  setModel = async (model: string) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ SetModel: { model } }) as Promise<void>;
+  }
+
+  setPrinterConnected = async (connected: boolean) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ SetPrinterConnected: { connected } }) as Promise<void>;
+  }
+
+  /**
+   * Returns the images printed since the last call as PNG files
+   */
+  takePrints = async () => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ TakePrints: {} }) as Promise<Uint8Array[]>;
+  }
+
+  /**
+   * Mutes or solos a sound channel (Square 1, Square 2, Wave or Noise).
+   * When any channel is soloed, only soloed channels are heard
+   */
+  setAudioChannel = async (channel: string, muted: boolean, solo: boolean) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ SetAudioChannel: { channel, muted, solo } }) as Promise<void>;
+  }
+
+  /**
+   * Starts playing given song of the loaded GBS file, counting from 0.
+   * Resolves with the song that is playing
+   */
+  selectTrack = async (track: number) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ SelectTrack: { track } }) as Promise<number>;
+  }
+
+  /**
+   * Starts playing the next song of the loaded GBS file, wrapping around to the first.
+   * Resolves with the song that is playing
+   */
+  nextTrack = async () => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ NextTrack: {} }) as Promise<number>;
+  }
+
+  /**
+   * Starts playing the previous song of the loaded GBS file, wrapping around to the last.
+   * Resolves with the song that is playing
+   */
+  previousTrack = async () => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ PreviousTrack: {} }) as Promise<number>;
+  }
+
+  /**
+   * Starts recording audio at given sample rate, including each sound channel separately if stems is set.
+   * Recording is independent of playback speed and continues across loaded ROMs
+   */
+  startRecording = async (sampleRate: number, stems: boolean) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ StartRecording: { sample_rate: sampleRate, stems } }) as Promise<void>;
+  }
+
+  /**
+   * Stops recording audio and returns it as WAV files in given format (PCM16 or Float32).
+   * The first file is the stereo mix, followed by Square 1, Square 2, Wave and Noise if stems were recorded
+   */
+  stopRecording = async (format: string) => {
+    if (!this.proxy) {
+      throw new ReferenceError("Emulator is not initialized");
+    }
+    return this.proxy.query({ StopRecording: { format } }) as Promise<Uint8Array[]>;
+  }
+
   updateInput = async (input: string, pressed: boolean) => {
     if (!this.proxy) {
       return;
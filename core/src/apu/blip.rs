@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+/// Amount of output samples each step is spread over
+const KERNEL_WIDTH: usize = 16;
+/// Amount of sub-sample positions a step can be placed at
+const KERNEL_PHASES: usize = 64;
+/// Cutoff frequency of the low-pass filter, relative to the output sample rate
+const CUTOFF: f64 = 0.45;
+
+type Kernel = [[f32; KERNEL_WIDTH]; KERNEL_PHASES];
+
+/// Returns the band-limited step for each sub-sample phase, as the change it makes to each of
+/// the output samples it's spread over. Steps are delayed by half the kernel width,
+/// so they never reach samples before the one they happen in
+fn kernel() -> &'static Kernel {
+    static KERNEL: OnceLock<Kernel> = OnceLock::new();
+    KERNEL.get_or_init(|| {
+        // Windowed sinc, which is integrated over each output sample
+        let half = (KERNEL_WIDTH / 2) as f64;
+        let impulse = |t: f64| {
+            if t.abs() >= half {
+                return 0.0;
+            }
+            let sinc = if t == 0.0 {
+                1.0
+            } else {
+                let x = std::f64::consts::PI * 2.0 * CUTOFF * t;
+                x.sin() / x
+            };
+            let window = 0.42
+                + 0.5 * (std::f64::consts::PI * t / half).cos()
+                + 0.08 * (2.0 * std::f64::consts::PI * t / half).cos();
+            2.0 * CUTOFF * sinc * window
+        };
+        const SUBSTEPS: usize = 32;
+        let mut kernel = [[0.0; KERNEL_WIDTH]; KERNEL_PHASES];
+        for (phase, taps) in kernel.iter_mut().enumerate() {
+            let center = half - 1.0 + phase as f64 / KERNEL_PHASES as f64;
+            let mut values = [0.0f64; KERNEL_WIDTH];
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = (0..SUBSTEPS)
+                    .map(|step| {
+                        let t = i as f64 - 1.0 + (step as f64 + 0.5) / SUBSTEPS as f64;
+                        impulse(t - center)
+                    })
+                    .sum::<f64>()
+                    / SUBSTEPS as f64;
+            }
+            // Every step has to add up to exactly its full height
+            let sum: f64 = values.iter().sum();
+            for (tap, value) in taps.iter_mut().zip(values) {
+                *tap = (value / sum) as f32;
+            }
+        }
+        kernel
+    })
+}
+
+/// Band-limited step synthesis in the style of blip_buf: changes in amplitude are added
+/// at the exact T-cycle they happen as band-limited steps, and read out at the host sample rate.
+/// Avoids the aliasing of point sampling, and only costs work when the amplitude changes
+pub struct BlipBuffer {
+    /// Output samples per T-cycle
+    ratio: f64,
+    /// Position of the start of the current frame in output samples
+    offset: f64,
+    /// Changes in amplitude of upcoming output samples
+    deltas: VecDeque<f32>,
+    /// Amplitude of the last read sample, which is the sum of all read changes
+    amplitude: f32,
+    /// Amplitude last set with `set_level`
+    level: f32,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            ratio: f64::from(sample_rate) / f64::from(clock_rate),
+            offset: 0.0,
+            deltas: VecDeque::from(vec![0.0; KERNEL_WIDTH]),
+            amplitude: 0.0,
+            level: 0.0,
+        }
+    }
+
+    /// Changes the output sample rate, keeping the changes that haven't been read yet
+    pub fn set_sample_rate(&mut self, clock_rate: u32, sample_rate: u32) {
+        self.ratio = f64::from(sample_rate) / f64::from(clock_rate);
+    }
+
+    /// Changes the amplitude by `delta` at given T-cycle of the current frame
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let position = self.offset + f64::from(time) * self.ratio;
+        let index = position as usize;
+        let phase = ((position.fract() * KERNEL_PHASES as f64) as usize).min(KERNEL_PHASES - 1);
+        let end = index + KERNEL_WIDTH;
+        if end > self.deltas.len() {
+            self.deltas.resize(end, 0.0);
+        }
+        for (sample, tap) in self.deltas.range_mut(index..end).zip(&kernel()[phase]) {
+            *sample += delta * tap;
+        }
+    }
+
+    /// Sets the amplitude at given T-cycle of the current frame
+    pub fn set_level(&mut self, time: u32, level: f32) {
+        if level != self.level {
+            self.add_delta(time, level - self.level);
+            self.level = level;
+        }
+    }
+
+    /// Ends the current frame after given amount of T-cycles,
+    /// making the samples before its end available for reading
+    pub fn end_frame(&mut self, time: u32) {
+        self.offset += f64::from(time) * self.ratio;
+    }
+
+    /// Returns the amount of samples that can be read
+    pub fn samples_available(&self) -> usize {
+        self.offset as usize
+    }
+
+    /// Reads the next sample. Should only be called while samples are available
+    pub fn read_sample(&mut self) -> f32 {
+        self.amplitude += self.deltas.pop_front().unwrap_or_default();
+        if self.deltas.len() < KERNEL_WIDTH {
+            self.deltas.push_back(0.0);
+        }
+        self.offset -= 1.0;
+        self.amplitude
+    }
+}
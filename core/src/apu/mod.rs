@@ -0,0 +1,1269 @@
+use super::*;
+
+mod blip;
+mod recorder;
+use blip::*;
+pub use recorder::*;
+
+pub trait Channel {
+    /// Reads value from channel register at global address
+    /// Note: Unused bits, length counters and frequencies are set to 1s when read back,
+    /// as defined here: https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Register_Reading
+    fn read_register(&self, address: u16) -> u8;
+
+    /// Writes value to channel register at global address.
+    /// `frame_step` is the next step of the frame sequencer, which affects length counter writes
+    fn write_register(&mut self, address: u16, value: u8, frame_step: u8);
+
+    /// Returns the next sample
+    fn get_sample(&self) -> f32;
+
+    /// Triggers the channel
+    /// (https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Trigger_Event)
+    fn trigger(&mut self);
+
+    /// Converts internal digital sample between 0 and 15
+    /// to an analog value between -1.0 and 1.0
+    fn convert_sample(&self, digital: u8) -> f32 {
+        ((digital as f32) / 7.5) - 1.0
+    }
+}
+
+/// Length counter, which turns its channel off once it runs out
+#[derive(Deserialize, Serialize)]
+pub struct LengthCounter {
+    pub counter: u16,
+    pub enabled: bool,
+    /// Length loaded by a trigger when the counter has run out: 64, or 256 on the wave channel
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            counter: 0,
+            enabled: false,
+            max,
+        }
+    }
+
+    /// Loads the counter from the length bits of NRx1
+    pub fn load(&mut self, length: u8) {
+        self.counter = self.max - u16::from(length);
+    }
+
+    /// Clocks the counter, returning true if it ran out and the channel should turn off
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            self.counter == 0
+        } else {
+            false
+        }
+    }
+
+    /// Handles a write to NRx4, returning true if the channel should turn off.
+    /// If the next frame sequencer step doesn't clock length, enabling the counter clocks it
+    /// right away, and so does a trigger reloading it
+    pub fn write_control(&mut self, value: u8, frame_step: u8) -> bool {
+        let enabled = value & 0b0100_0000 > 0;
+        let trigger = value & 0b1000_0000 > 0;
+        let extra_clock = frame_step % 2 == 1;
+        let mut ran_out = false;
+        if !self.enabled && enabled && extra_clock && self.counter > 0 {
+            self.counter -= 1;
+            ran_out = self.counter == 0 && !trigger;
+        }
+        self.enabled = enabled;
+        if trigger && self.counter == 0 {
+            self.counter = self.max;
+            if enabled && extra_clock {
+                self.counter -= 1;
+            }
+        }
+        ran_out
+    }
+}
+
+/// Volume envelope of the square and noise channels, set with NRx2
+#[derive(Deserialize, Serialize)]
+pub struct VolumeEnvelope {
+    // State variables
+    pub volume: u8,
+    pub timer: u8,
+    /// If the envelope still changes the volume, which stops once it would leave 0-15
+    pub running: bool,
+    // Register variables
+    pub initial_volume: u8,
+    pub increase: bool,
+    pub pace: u8,
+}
+
+impl VolumeEnvelope {
+    pub fn new() -> Self {
+        Self {
+            volume: 0,
+            timer: 0,
+            running: false,
+            initial_volume: 0,
+            increase: false,
+            pace: 0,
+        }
+    }
+
+    /// Returns the value of NRx2
+    pub fn read(&self) -> u8 {
+        (self.initial_volume << 4) | ((self.increase as u8) << 3) | self.pace
+    }
+
+    /// Writes NRx2. Writing while the channel is on changes the current volume
+    /// ("zombie mode"), which some games use to change volume without retriggering
+    pub fn write(&mut self, value: u8, channel_on: bool) {
+        let increase = value & 0b1000 > 0;
+        if channel_on {
+            if self.pace == 0 && self.running {
+                self.volume += 1;
+            } else if !self.increase {
+                self.volume += 2;
+            }
+            if increase != self.increase {
+                self.volume = 16u8.wrapping_sub(self.volume);
+            }
+            self.volume &= 0xF;
+        }
+        self.initial_volume = value >> 4;
+        self.increase = increase;
+        self.pace = value & 0b0111;
+    }
+
+    /// Channel volume unit is controlled by the NRx2 bits:
+    /// if the envelope is set to decrease volume from 0, the DAC is off
+    pub fn dac_on(&self) -> bool {
+        self.initial_volume > 0 || self.increase
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.pace;
+        self.running = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.pace == 0 || !self.running {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.pace;
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        } else {
+            self.running = false;
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SquareChannel {
+    // State variables
+    pub channel_on: bool,
+    pub dac_on: bool,
+    pub period_div: u16,
+    pub duty_cycle_pointer: u8,
+    pub length: LengthCounter,
+    pub envelope: VolumeEnvelope,
+    pub sweep_timer: u8,
+    /// If the sweep runs, which is decided on trigger by its pace and step
+    pub sweep_enabled: bool,
+    /// Copy of the period the sweep calculates new periods from
+    pub sweep_shadow: u16,
+    /// If a sweep calculation has decreased the period since the last trigger
+    pub sweep_negated: bool,
+    // Register variables
+    pub sweep_pace: u8,
+    pub sweep_increase: bool,
+    pub sweep_step: u8,
+    pub duty_cycle_index: u8,
+    pub period: u16,
+}
+
+impl SquareChannel {
+    pub fn new() -> Self {
+        Self {
+            channel_on: false,
+            dac_on: false,
+            period_div: 0,
+            duty_cycle_pointer: 0,
+            length: LengthCounter::new(64),
+            envelope: VolumeEnvelope::new(),
+            sweep_timer: 0,
+            sweep_enabled: false,
+            sweep_shadow: 0,
+            sweep_negated: false,
+
+            sweep_pace: 0,
+            sweep_increase: true,
+            sweep_step: 0,
+            duty_cycle_index: 0,
+            period: 0,
+        }
+    }
+
+    pub fn update_length_timer(&mut self) {
+        if self.length.clock() {
+            self.channel_on = false;
+        }
+    }
+
+    /// Calculates the next period of the sweep, turning the channel off if it overflows
+    fn sweep_calculation(&mut self) -> u16 {
+        let period_change = self.sweep_shadow >> self.sweep_step;
+        let period = if self.sweep_increase {
+            self.sweep_shadow + period_change
+        } else {
+            self.sweep_negated = true;
+            self.sweep_shadow - period_change
+        };
+        if period > 0x7FF {
+            self.channel_on = false;
+        }
+        period
+    }
+
+    pub fn update_sweep(&mut self) {
+        self.sweep_timer = self.sweep_timer.saturating_sub(1);
+        if self.sweep_timer > 0 {
+            return;
+        }
+        // Pace 0 is treated as 8 by the timer, but doesn't change the period
+        self.sweep_timer = if self.sweep_pace == 0 {
+            8
+        } else {
+            self.sweep_pace
+        };
+        if self.sweep_enabled && self.sweep_pace > 0 {
+            let period = self.sweep_calculation();
+            if period <= 0x7FF && self.sweep_step > 0 {
+                self.sweep_shadow = period;
+                self.period = period;
+                // New period is checked for overflow again, but not written back
+                self.sweep_calculation();
+            }
+        }
+    }
+
+    pub fn update_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Advances the period divider, returning true if the channel moved to the next step
+    pub fn update_period(&mut self) -> bool {
+        if self.period_div == 0x7FF {
+            if self.duty_cycle_pointer == 7 {
+                self.duty_cycle_pointer = 0;
+            } else {
+                self.duty_cycle_pointer += 1;
+            }
+            self.period_div = self.period;
+            true
+        } else {
+            self.period_div += 1;
+            false
+        }
+    }
+
+    fn get_duty_cycle_val(&self, index: u8) -> u8 {
+        let duty_cycle = match self.duty_cycle_index {
+            // 12.5 %
+            0 => [1, 1, 1, 1, 1, 1, 1, 0],
+            // 25 %
+            1 => [0, 1, 1, 1, 1, 1, 1, 0],
+            // 50 %
+            2 => [0, 1, 1, 1, 1, 0, 0, 0],
+            // 75 %
+            3 => [1, 0, 0, 0, 0, 0, 0, 1],
+            _ => unreachable!(),
+        };
+        duty_cycle[index as usize]
+    }
+}
+
+impl Channel for SquareChannel {
+    fn read_register(&self, address: u16) -> u8 {
+        match address {
+            // NR10 (Sweep)
+            0xFF10 => {
+                (self.sweep_pace << 4)
+                    | ((!self.sweep_increase as u8) << 3)
+                    | self.sweep_step
+                    | 0x80
+            }
+            // NR11 / NR21 (Length and duty cycle)
+            0xFF11 | 0xFF16 => (self.duty_cycle_index << 6) | 0x3F,
+            // NR12 / NR22 (Volume and envelope)
+            0xFF12 | 0xFF17 => self.envelope.read(),
+            // NR13 / NR23 (Period low bits)
+            0xFF13 | 0xFF18 => 0xFF,
+            // NR14 / NR14 (Control and period high bits)
+            0xFF14 | 0xFF19 => ((self.length.enabled as u8) << 6) | 0xBF,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8, frame_step: u8) {
+        match address {
+            // NR10 (Sweep)
+            0xFF10 => {
+                self.sweep_pace = (value >> 4) & 0b111;
+                // 0 == increase
+                self.sweep_increase = value & 0b1000 == 0;
+                self.sweep_step = value & 0b0111;
+                // Leaving negate mode after a calculation has used it turns the channel off
+                if self.sweep_increase && self.sweep_negated {
+                    self.channel_on = false;
+                }
+            }
+            // NR11 / NR21 (Length and duty cycle)
+            0xFF11 | 0xFF16 => {
+                self.duty_cycle_index = value >> 6;
+                self.length.load(value & 0b11_1111);
+            }
+            // NR12 / NR22 (Volume and envelope)
+            0xFF12 | 0xFF17 => {
+                self.envelope.write(value, self.channel_on);
+                self.dac_on = self.envelope.dac_on();
+                if !self.dac_on {
+                    self.channel_on = false;
+                }
+            }
+            // NR13 / NR23 (Period low bits)
+            0xFF13 | 0xFF18 => self.period = (self.period & 0xFF00) | value as u16,
+            // NR14 / NR14 (Control and period high bits)
+            0xFF14 | 0xFF19 => {
+                self.period = (self.period & 0xFF) | (((value & 0b111) as u16) << 8);
+                if self.length.write_control(value, frame_step) {
+                    self.channel_on = false;
+                }
+                if value & 0b1000_0000 > 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.channel_on = self.dac_on;
+        self.period_div = self.period;
+        self.envelope.trigger();
+        self.sweep_shadow = self.period;
+        self.sweep_timer = if self.sweep_pace == 0 {
+            8
+        } else {
+            self.sweep_pace
+        };
+        self.sweep_enabled = self.sweep_pace > 0 || self.sweep_step > 0;
+        self.sweep_negated = false;
+        // Overflow is checked right away if the sweep changes the period
+        if self.sweep_step > 0 {
+            self.sweep_calculation();
+        }
+    }
+
+    fn get_sample(&self) -> f32 {
+        if self.dac_on {
+            let volume = if self.channel_on {
+                self.envelope.volume * self.get_duty_cycle_val(self.duty_cycle_pointer)
+            } else {
+                0
+            };
+            self.convert_sample(volume)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WaveChannel {
+    // State variables
+    pub dac_on: bool,
+    pub channel_on: bool,
+    pub period_div: u16,
+    pub wave_pointer: u8,
+    pub length: LengthCounter,
+    pub output_level: u8,
+    /// Wave RAM byte the current sample is played from
+    pub sample_buffer: u8,
+    /// Wave ticks left before the channel starts counting its period after a trigger
+    pub trigger_delay: u8,
+    /// If the channel read wave RAM on its latest tick, which is the only time the CPU
+    /// can access wave RAM while the channel plays on DMG
+    pub reading_wave_ram: bool,
+    // Register variables
+    pub period: u16,
+    pub wave_ram: [u8; 0x10],
+}
+
+impl WaveChannel {
+    pub fn new() -> Self {
+        Self {
+            dac_on: false,
+            channel_on: false,
+            period_div: 0,
+            wave_pointer: 0,
+            length: LengthCounter::new(256),
+            output_level: 0,
+            sample_buffer: 0,
+            trigger_delay: 0,
+            reading_wave_ram: false,
+
+            period: 0,
+            wave_ram: [0; 0x10],
+        }
+    }
+
+    pub fn update_length_timer(&mut self) {
+        if self.length.clock() {
+            self.channel_on = false;
+        }
+    }
+
+    /// Advances the period divider, returning true if the channel read the next sample
+    pub fn update_period(&mut self) -> bool {
+        self.reading_wave_ram = false;
+        if !self.channel_on {
+            return false;
+        }
+        if self.trigger_delay > 0 {
+            self.trigger_delay -= 1;
+            return false;
+        }
+        if self.period_div == 0x7FF {
+            if self.wave_pointer == 31 {
+                self.wave_pointer = 0;
+            } else {
+                self.wave_pointer += 1;
+            }
+            self.sample_buffer = self.wave_ram[(self.wave_pointer / 2) as usize];
+            self.reading_wave_ram = true;
+            self.period_div = self.period;
+            true
+        } else {
+            self.period_div += 1;
+            false
+        }
+    }
+
+    /// Returns the wave RAM byte the CPU accesses at given address, if any.
+    /// While the channel plays, accesses go to the byte it's playing from,
+    /// and on DMG they only reach it while the channel reads it
+    fn wave_ram_index(&self, address: u16, cgb: bool) -> Option<usize> {
+        if !self.channel_on {
+            Some((address - 0xFF30) as usize)
+        } else if cgb || self.reading_wave_ram {
+            Some((self.wave_pointer / 2) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn read_wave_ram(&self, address: u16, cgb: bool) -> u8 {
+        self.wave_ram_index(address, cgb)
+            .map_or(0xFF, |index| self.wave_ram[index])
+    }
+
+    pub fn write_wave_ram(&mut self, address: u16, value: u8, cgb: bool) {
+        if let Some(index) = self.wave_ram_index(address, cgb) {
+            self.wave_ram[index] = value;
+        }
+    }
+
+    /// Retriggering the channel on DMG right as it reads a sample overwrites the start of
+    /// wave RAM: with the byte being read if it's one of the first four, otherwise with
+    /// the four aligned bytes it belongs to
+    pub fn corrupt_wave_ram(&mut self) {
+        if !self.channel_on || self.trigger_delay > 0 || self.period_div != 0x7FF {
+            return;
+        }
+        let index = (((self.wave_pointer + 1) % 32) / 2) as usize;
+        if index < 4 {
+            self.wave_ram[0] = self.wave_ram[index];
+        } else {
+            let start = index & !0b11;
+            self.wave_ram.copy_within(start..start + 4, 0);
+        }
+    }
+}
+
+impl Channel for WaveChannel {
+    fn read_register(&self, address: u16) -> u8 {
+        // Unused bits, length counters and frequencies are set to 1s when read back,
+        // as defined here: https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Register_Reading
+        match address {
+            // NR30 (DAC)
+            0xFF1A => (self.dac_on as u8) << 7 | 0x7F,
+            // NR31 (Length timer)
+            0xFF1B => 0xFF,
+            // NR32 (Output level)
+            0xFF1C => self.output_level << 5 | 0x9F,
+            // NR33 (Period low bits)
+            0xFF1D => 0xFF,
+            // NR34 (Control and period high bits)
+            0xFF1E => ((self.length.enabled as u8) << 6) | 0xBF,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8, frame_step: u8) {
+        match address {
+            // NR30 (DAC)
+            0xFF1A => {
+                self.dac_on = value & 0b1000_0000 > 0;
+                if !self.dac_on {
+                    self.channel_on = false;
+                }
+            }
+            // NR31 (Length timer)
+            0xFF1B => self.length.load(value),
+            // NR32 (Output level)
+            0xFF1C => self.output_level = (value >> 5) & 0b11,
+            // NR33 (Period low bits)
+            0xFF1D => self.period = (self.period & 0xFF00) | value as u16,
+            // NR34 (Control and period high bits)
+            0xFF1E => {
+                self.period = (self.period & 0xFF) | (((value & 0b111) as u16) << 8);
+                if self.length.write_control(value, frame_step) {
+                    self.channel_on = false;
+                }
+                if value & 0b1000_0000 > 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.channel_on = self.dac_on;
+        self.period_div = self.period;
+        // First sample is read 6 T-cycles later than the period would say, and until then
+        // the channel keeps playing the sample it read before
+        self.trigger_delay = 3;
+        self.wave_pointer = 0;
+    }
+
+    fn get_sample(&self) -> f32 {
+        if self.dac_on {
+            let volume = if self.channel_on {
+                let nibble = if self.wave_pointer & 1 == 0 {
+                    self.sample_buffer >> 4
+                } else {
+                    self.sample_buffer & 0xF
+                };
+                match self.output_level {
+                    0 => 0,
+                    1 => nibble,
+                    2 => nibble >> 1,
+                    3 => nibble >> 2,
+                    _ => unreachable!(),
+                }
+            } else {
+                0
+            };
+            self.convert_sample(volume)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NoiseChannel {
+    // State variables
+    pub dac_on: bool,
+    pub channel_on: bool,
+    pub duty_cycle_pointer: u8,
+    pub length: LengthCounter,
+    pub envelope: VolumeEnvelope,
+    pub lfsr: u16,
+    pub lfsr_bit: bool,
+    pub lfsr_timer: u32,
+    pub lfsr_pace: u32,
+    // Register variables
+    pub clock_shift: u8,
+    pub short_lfsr: bool,
+    pub clock_divider: u8,
+}
+
+impl NoiseChannel {
+    pub fn new() -> Self {
+        Self {
+            channel_on: false,
+            dac_on: false,
+            duty_cycle_pointer: 0,
+            length: LengthCounter::new(64),
+            envelope: VolumeEnvelope::new(),
+            lfsr: 0,
+            lfsr_bit: false,
+            lfsr_timer: 1,
+            lfsr_pace: 1,
+
+            clock_shift: 0,
+            short_lfsr: false,
+            clock_divider: 0,
+        }
+    }
+
+    pub fn update_length_timer(&mut self) {
+        if self.length.clock() {
+            self.channel_on = false;
+        }
+    }
+
+    pub fn update_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Advances the LFSR timer, returning true if the LFSR was shifted
+    pub fn update_lfsr(&mut self) -> bool {
+        if self.lfsr_timer < self.lfsr_pace {
+            self.lfsr_timer += 1;
+            false
+        } else {
+            self.lfsr_timer = 1;
+            let xnor = self.lfsr & 0b1 == (self.lfsr & 0b10) >> 1;
+            if xnor {
+                self.lfsr |= (xnor as u16) << 15;
+                if self.short_lfsr {
+                    self.lfsr |= (xnor as u16) << 7;
+                }
+            }
+            self.lfsr >>= 1;
+            self.lfsr_bit = self.lfsr & 0b1 > 0;
+            true
+        }
+    }
+}
+
+impl Channel for NoiseChannel {
+    fn read_register(&self, address: u16) -> u8 {
+        // Unused bits, length counters and frequencies are set to 1s when read back,
+        // as defined here: https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Register_Reading
+        match address {
+            // NR41 (Length timer)
+            0xFF20 => 0xFF,
+            // NR42 (Volume and envelope)
+            0xFF21 => self.envelope.read(),
+            // NR43 (Frequency and randomness)
+            0xFF22 => (self.clock_shift << 4) | ((self.short_lfsr as u8) << 3) | self.clock_divider,
+            // NR44 (Control)
+            0xFF23 => (self.length.enabled as u8) << 6 | 0xBF,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8, frame_step: u8) {
+        match address {
+            // NR41 (Length timer)
+            0xFF20 => self.length.load(value & 0b0011_1111),
+            // NR42 (Volume and envelope)
+            0xFF21 => {
+                self.envelope.write(value, self.channel_on);
+                self.dac_on = self.envelope.dac_on();
+                if !self.dac_on {
+                    self.channel_on = false;
+                }
+            }
+            // NR43 (Frequency and randomness)
+            0xFF22 => {
+                self.clock_shift = value >> 4;
+                self.short_lfsr = value & 0b1000 > 0;
+                self.clock_divider = value & 0b0111;
+                // Divider value 0 is treated as 0.5
+                self.lfsr_pace = if self.clock_divider == 0 {
+                    2u32.pow(self.clock_shift as u32) / 2
+                } else {
+                    (self.clock_divider as u32) * 2u32.pow(self.clock_shift as u32)
+                };
+            }
+            // NR44 (Control)
+            0xFF23 => {
+                if self.length.write_control(value, frame_step) {
+                    self.channel_on = false;
+                }
+                if value & 0b1000_0000 > 0 {
+                    self.trigger();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.channel_on = self.dac_on;
+        self.lfsr = 0;
+        self.envelope.trigger();
+    }
+
+    fn get_sample(&self) -> f32 {
+        if self.dac_on {
+            let volume = if self.channel_on && self.lfsr_bit {
+                self.envelope.volume
+            } else {
+                0
+            };
+            self.convert_sample(volume)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct PanRegister(u8);
+
+bitflags! {
+    impl PanRegister: u8 {
+        const CH4_LEFT  = 0b1000_0000;
+        const CH3_LEFT  = 0b0100_0000;
+        const CH2_LEFT  = 0b0010_0000;
+        const CH1_LEFT  = 0b0001_0000;
+        const CH4_RIGHT = 0b0000_1000;
+        const CH3_RIGHT = 0b0000_0100;
+        const CH2_RIGHT = 0b0000_0010;
+        const CH1_RIGHT = 0b0000_0001;
+    }
+}
+
+use ringbuf::{
+    HeapRb, SharedRb,
+    storage::Heap,
+    traits::{Producer, Split},
+    wrap::caching::Caching,
+};
+use std::sync::Arc;
+
+pub type AudioBufferProducer = Caching<Arc<SharedRb<Heap<f32>>>, true, false>;
+pub type AudioBufferConsumer = Caching<Arc<SharedRb<Heap<f32>>>, false, true>;
+
+/// One of the four sound channels of the APU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioChannel {
+    Square1,
+    Square2,
+    Wave,
+    Noise,
+}
+
+impl AudioChannel {
+    pub const ALL: [AudioChannel; 4] = [
+        AudioChannel::Square1,
+        AudioChannel::Square2,
+        AudioChannel::Wave,
+        AudioChannel::Noise,
+    ];
+}
+
+/// Separate outputs of each channel, taken after the DAC and before panning and volume.
+/// The outputs keep the sample rate they were created with, independent of playback
+struct ChannelTaps {
+    outputs: [BlipBuffer; 4],
+    buffer_producers: [AudioBufferProducer; 4],
+}
+
+/// Audio processing unit
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub struct APU {
+    #[serde(skip)]
+    buffer_producer: Option<AudioBufferProducer>,
+    #[serde(skip)]
+    pub hpf_capacitor_charge_factor: f32,
+    #[serde(skip)]
+    pub channels: usize,
+    #[serde(skip)]
+    sample_rate: u32,
+    /// Band-limited left and right outputs, created once the sample rate is set
+    #[serde(skip)]
+    blip: Option<[BlipBuffer; 2]>,
+    /// Separate output of each channel, if requested by the host
+    #[serde(skip)]
+    channel_taps: Option<ChannelTaps>,
+    /// Outputs of the audio recording in progress, if any
+    #[serde(skip)]
+    recorder: Option<Recorder>,
+    /// T-cycles since the band-limited outputs were last read
+    #[serde(skip)]
+    frame_cycles: u32,
+    /// If the channel outputs may have changed since the amplitudes were last calculated
+    #[serde(skip)]
+    output_changed: bool,
+    /// Channels left out of the mix by the host
+    #[serde(skip)]
+    muted: [bool; 4],
+    /// Channels played alone by the host. If any channel is soloed, the rest are left out
+    #[serde(skip)]
+    soloed: [bool; 4],
+
+    /// If emulating Game Boy Color hardware, which lacks some of the DMG quirks
+    pub cgb: bool,
+    pub on: bool,
+    pub period_delay_counter: u8,
+    /// Next step of the frame sequencer, which is clocked by DIV-APU
+    pub div_apu: u8,
+    pub pan_options: PanRegister,
+    pub left_volume: u8,
+    pub right_volume: u8,
+    /// VIN bits of NR50, which mix cartridge audio into the output
+    pub vin_panning: u8,
+    pub left_hpf_capacitor: f32,
+    pub right_hpf_capacitor: f32,
+
+    pub square_channel_1: SquareChannel,
+    pub square_channel_2: SquareChannel,
+    pub wave_channel: WaveChannel,
+    pub noise_channel: NoiseChannel,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        Self {
+            buffer_producer: None,
+            channels: 0,
+            hpf_capacitor_charge_factor: 0.0,
+            sample_rate: 0,
+            blip: None,
+            channel_taps: None,
+            recorder: None,
+            frame_cycles: 0,
+            output_changed: false,
+            muted: [false; 4],
+            soloed: [false; 4],
+
+            cgb: false,
+            on: true,
+            period_delay_counter: 0,
+            div_apu: 0,
+            pan_options: PanRegister::from_bits_truncate(0),
+            left_volume: 1,
+            right_volume: 1,
+            vin_panning: 0,
+            left_hpf_capacitor: 0.0,
+            right_hpf_capacitor: 0.0,
+
+            square_channel_1: SquareChannel::new(),
+            square_channel_2: SquareChannel::new(),
+            wave_channel: WaveChannel::new(),
+            noise_channel: NoiseChannel::new(),
+        }
+    }
+
+    const CLOCK_SPEED: u32 = 4194304;
+    const CAPACITOR_CHARGE_FACTOR: f64 = 0.999958;
+    /// T-cycles between reading samples from the band-limited outputs into the audio buffer
+    const FRAME_CYCLES: u32 = 1024;
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        match &mut self.blip {
+            Some(outputs) => {
+                for output in outputs {
+                    output.set_sample_rate(Self::CLOCK_SPEED, sample_rate);
+                }
+            }
+            None => {
+                self.blip = Some([
+                    BlipBuffer::new(Self::CLOCK_SPEED, sample_rate),
+                    BlipBuffer::new(Self::CLOCK_SPEED, sample_rate),
+                ]);
+                self.output_changed = true;
+            }
+        }
+        self.hpf_capacitor_charge_factor = Self::capacitor_charge_factor(sample_rate);
+    }
+
+    /// Returns how much of its charge the high-pass filter capacitor keeps per sample
+    fn capacitor_charge_factor(sample_rate: u32) -> f32 {
+        Self::CAPACITOR_CHARGE_FACTOR.powf((Self::CLOCK_SPEED as f64) / (sample_rate as f64)) as f32
+    }
+
+    /// Applies a high-pass filter to a sample by simulating a capacitor
+    fn high_pass(sample: f32, capacitor: &mut f32, charge_factor: f32) -> f32 {
+        let output = sample - *capacitor;
+        *capacitor = sample - output * charge_factor;
+        output
+    }
+
+    pub fn init_buffer(&mut self, sample_capacity: usize, channels: usize) -> AudioBufferConsumer {
+        let ring = HeapRb::<f32>::new(sample_capacity);
+        let (producer, consumer) = ring.split();
+
+        self.channels = channels;
+        self.buffer_producer = Some(producer);
+        consumer
+    }
+
+    /// Creates a separate mono ring buffer for each channel at given sample rate
+    /// and returns their consumers
+    pub fn init_channel_buffers(
+        &mut self,
+        sample_capacity: usize,
+        sample_rate: u32,
+    ) -> [AudioBufferConsumer; 4] {
+        let (buffer_producers, consumers): (Vec<_>, Vec<_>) = (0..4)
+            .map(|_| HeapRb::<f32>::new(sample_capacity).split())
+            .unzip();
+        self.channel_taps = Some(ChannelTaps {
+            outputs: std::array::from_fn(|_| BlipBuffer::new(Self::CLOCK_SPEED, sample_rate)),
+            buffer_producers: buffer_producers.try_into().ok().unwrap(),
+        });
+        self.output_changed = true;
+        consumers.try_into().ok().unwrap()
+    }
+
+    /// Leaves a channel out of the mix or adds it back
+    pub fn set_muted(&mut self, channel: AudioChannel, muted: bool) {
+        self.muted[channel as usize] = muted;
+        self.output_changed = true;
+    }
+
+    /// Plays a channel alone, together with the other soloed channels
+    pub fn set_soloed(&mut self, channel: AudioChannel, soloed: bool) {
+        self.soloed[channel as usize] = soloed;
+        self.output_changed = true;
+    }
+
+    /// Starts adding the output to given recording, replacing the previous one
+    pub fn start_recording(&mut self, recording: AudioRecording) {
+        self.recorder = Some(Recorder::new(recording));
+        self.output_changed = true;
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Cycles the APU forward by one T-cycle.
+    /// The frame sequencer is stepped if `div_apu_tick` is set, which happens when DIV register
+    /// bit 4 (actual divider bit 12) goes from 1 to 0
+    pub fn cycle(&mut self, div_apu_tick: bool) {
+        if div_apu_tick && self.on {
+            self.step_frame_sequencer();
+            self.output_changed = true;
+        }
+
+        self.period_delay_counter = self.period_delay_counter.wrapping_add(1);
+        // Update wave channel period every 2 T-cycles
+        if self.period_delay_counter.is_multiple_of(2) {
+            self.output_changed |= self.wave_channel.update_period();
+            // Update square channel period every 4 T-cycles
+            if self.period_delay_counter.is_multiple_of(4) {
+                self.output_changed |= self.square_channel_1.update_period();
+                self.output_changed |= self.square_channel_2.update_period();
+                // Update noise channel frequency every 16 T-cycles
+                if self.period_delay_counter.is_multiple_of(16) {
+                    self.output_changed |= self.noise_channel.update_lfsr();
+                }
+            }
+        }
+
+        // Output is only mixed again when a channel may have changed,
+        // and the change is placed at this exact T-cycle
+        if std::mem::take(&mut self.output_changed) {
+            self.update_output();
+        }
+
+        self.frame_cycles += 1;
+        if self.frame_cycles == Self::FRAME_CYCLES {
+            self.end_frame();
+        }
+    }
+
+    /// Returns the DAC output of each channel
+    fn channel_samples(&self) -> [f32; 4] {
+        if !self.on {
+            return [0.0; 4];
+        }
+        [
+            self.square_channel_1.get_sample(),
+            self.square_channel_2.get_sample(),
+            self.wave_channel.get_sample(),
+            self.noise_channel.get_sample(),
+        ]
+    }
+
+    /// Returns the mixed left and right amplitudes of given channel outputs,
+    /// leaving out the channels muted by the host
+    fn mix(&self, samples: [f32; 4]) -> [f32; 2] {
+        const PANNING: [(PanRegister, PanRegister); 4] = [
+            (PanRegister::CH1_LEFT, PanRegister::CH1_RIGHT),
+            (PanRegister::CH2_LEFT, PanRegister::CH2_RIGHT),
+            (PanRegister::CH3_LEFT, PanRegister::CH3_RIGHT),
+            (PanRegister::CH4_LEFT, PanRegister::CH4_RIGHT),
+        ];
+        let any_soloed = self.soloed.contains(&true);
+
+        // Combine left and right channels
+        let mut left_sample = 0f32;
+        let mut right_sample = 0f32;
+        for (i, (sample, (left, right))) in samples.into_iter().zip(PANNING).enumerate() {
+            if self.muted[i] || (any_soloed && !self.soloed[i]) {
+                continue;
+            }
+            if self.pan_options.intersects(left) {
+                left_sample += sample;
+            }
+            if self.pan_options.intersects(right) {
+                right_sample += sample;
+            }
+        }
+
+        // Apply volume and scale final mixed sample in between -1.0 and 1.0
+        // Maximum analog value can be:
+        // +1.0 (max channel output voltage)
+        // * 4  (max amount of channels outputting at once)
+        // * 8  (max master volume)
+        [
+            left_sample * self.left_volume as f32 / 32.0,
+            right_sample * self.right_volume as f32 / 32.0,
+        ]
+    }
+
+    /// Adds the changes in the channel outputs to the band-limited outputs
+    fn update_output(&mut self) {
+        let samples = self.channel_samples();
+        let levels = self.mix(samples);
+        if let Some(outputs) = &mut self.blip {
+            for (output, level) in outputs.iter_mut().zip(levels) {
+                output.set_level(self.frame_cycles, level);
+            }
+        }
+        if let Some(taps) = &mut self.channel_taps {
+            for (output, sample) in taps.outputs.iter_mut().zip(samples) {
+                output.set_level(self.frame_cycles, sample);
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.set_levels(self.frame_cycles, levels, samples);
+        }
+    }
+
+    /// Reads the samples finished during the frame from the band-limited outputs
+    /// into the audio buffer
+    fn end_frame(&mut self) {
+        let frame_cycles = std::mem::take(&mut self.frame_cycles);
+        if let Some(taps) = &mut self.channel_taps {
+            for (output, buffer) in taps.outputs.iter_mut().zip(&mut taps.buffer_producers) {
+                output.end_frame(frame_cycles);
+                while output.samples_available() > 0 {
+                    let _ = buffer.try_push(output.read_sample());
+                }
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.end_frame(frame_cycles);
+        }
+        let Some([left, right]) = &mut self.blip else {
+            return;
+        };
+        left.end_frame(frame_cycles);
+        right.end_frame(frame_cycles);
+        while left.samples_available() > 0 {
+            let left_sample = left.read_sample();
+            let right_sample = right.read_sample();
+
+            let left_output = Self::high_pass(
+                left_sample,
+                &mut self.left_hpf_capacitor,
+                self.hpf_capacitor_charge_factor,
+            );
+            let right_output = Self::high_pass(
+                right_sample,
+                &mut self.right_hpf_capacitor,
+                self.hpf_capacitor_charge_factor,
+            );
+
+            let Some(buffer) = &mut self.buffer_producer else {
+                continue;
+            };
+            // If output has two channels, send sound as stereo
+            if self.channels == 2 {
+                let _ = buffer.try_push(left_output);
+                let _ = buffer.try_push(right_output);
+            }
+            // Otherwise merge sound into mono
+            else {
+                for _ in 0..self.channels {
+                    let _ = buffer.try_push((left_output / 2.0) + (right_output / 2.0));
+                }
+            }
+        }
+    }
+
+    /// Runs the next step of the frame sequencer.
+    /// Length timers are updated at 256hz (steps 0, 2, 4 and 6), CH1 period sweep at 128hz
+    /// (steps 2 and 6) and envelopes at 64hz (step 7)
+    fn step_frame_sequencer(&mut self) {
+        let step = self.div_apu;
+        self.div_apu = (self.div_apu + 1) % 8;
+        if step.is_multiple_of(2) {
+            self.square_channel_1.update_length_timer();
+            self.square_channel_2.update_length_timer();
+            self.wave_channel.update_length_timer();
+            self.noise_channel.update_length_timer();
+        }
+        if step == 2 || step == 6 {
+            self.square_channel_1.update_sweep();
+        }
+        if step == 7 {
+            self.square_channel_1.update_envelope();
+            self.square_channel_2.update_envelope();
+            self.noise_channel.update_envelope();
+        }
+    }
+
+    fn turn_off(&mut self) {
+        self.on = false;
+        // Reset registers, so the frame sequencer starts from step 0 when turned back on
+        self.period_delay_counter = 0;
+        self.div_apu = 0;
+        self.pan_options = PanRegister::from_bits_truncate(0);
+        self.left_volume = 1;
+        self.right_volume = 1;
+        self.vin_panning = 0;
+        // Reset channel registers. Length counters are unaffected on DMG
+        let lengths = [
+            self.square_channel_1.length.counter,
+            self.square_channel_2.length.counter,
+            self.wave_channel.length.counter,
+            self.noise_channel.length.counter,
+        ];
+        self.square_channel_1 = SquareChannel::new();
+        self.square_channel_2 = SquareChannel::new();
+        let wave_ram = self.wave_channel.wave_ram;
+        self.wave_channel = WaveChannel::new();
+        self.wave_channel.wave_ram = wave_ram;
+        self.noise_channel = NoiseChannel::new();
+        if !self.cgb {
+            self.square_channel_1.length.counter = lengths[0];
+            self.square_channel_2.length.counter = lengths[1];
+            self.wave_channel.length.counter = lengths[2];
+            self.noise_channel.length.counter = lengths[3];
+        }
+    }
+
+    /// Handles a write while the APU is off, which only reaches master control, wave RAM
+    /// and, on DMG, the length counters. Returns true if the write goes through
+    fn write_while_off(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0xFF26 | 0xFF30..=0xFF3F => true,
+            _ if self.cgb => false,
+            0xFF11 => {
+                self.square_channel_1.length.load(value & 0b11_1111);
+                false
+            }
+            0xFF16 => {
+                self.square_channel_2.length.load(value & 0b11_1111);
+                false
+            }
+            0xFF1B => {
+                self.wave_channel.length.load(value);
+                false
+            }
+            0xFF20 => {
+                self.noise_channel.length.load(value & 0b11_1111);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MemoryAccess for APU {
+    fn mem_read(&self, address: u16) -> u8 {
+        match address {
+            // NR10 - NR14
+            0xFF10..=0xFF14 => self.square_channel_1.read_register(address),
+            // NR21 - NR24
+            0xFF16..=0xFF19 => self.square_channel_2.read_register(address),
+            // NR30 - NR34
+            0xFF1A..=0xFF1E => self.wave_channel.read_register(address),
+            // Wave RAM
+            0xFF30..=0xFF3F => self.wave_channel.read_wave_ram(address, self.cgb),
+            // NR41 - NR44
+            0xFF20..=0xFF23 => self.noise_channel.read_register(address),
+            // NR50 - Master volume
+            0xFF24 => self.vin_panning | ((self.left_volume - 1) << 4) | (self.right_volume - 1),
+            // NR51 - Sound panning
+            0xFF25 => self.pan_options.bits(),
+            // NR52 - Master control
+            0xFF26 => {
+                ((self.on as u8) << 7)
+                    | ((self.noise_channel.channel_on as u8) << 3)
+                    | ((self.wave_channel.channel_on as u8) << 2)
+                    | ((self.square_channel_2.channel_on as u8) << 1)
+                    | (self.square_channel_1.channel_on as u8)
+                    | 0x70
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn mem_write(&mut self, address: u16, value: u8) {
+        // Registers apart from master control and wave RAM cant be written to
+        // when APU is turned off
+        if !self.on && !self.write_while_off(address, value) {
+            return;
+        }
+        let frame_step = self.div_apu;
+        self.output_changed = true;
+        match address {
+            // NR10 - NR14
+            0xFF10..=0xFF14 => {
+                self.square_channel_1
+                    .write_register(address, value, frame_step);
+            }
+            // NR21 - NR24
+            0xFF16..=0xFF19 => {
+                self.square_channel_2
+                    .write_register(address, value, frame_step);
+            }
+            // NR30 - NR34
+            0xFF1A..=0xFF1E => {
+                if address == 0xFF1E && value & 0b1000_0000 > 0 && !self.cgb {
+                    self.wave_channel.corrupt_wave_ram();
+                }
+                self.wave_channel.write_register(address, value, frame_step);
+            }
+            // Wave RAM
+            0xFF30..=0xFF3F => self.wave_channel.write_wave_ram(address, value, self.cgb),
+            // NR41 - NR44
+            0xFF20..=0xFF23 => self
+                .noise_channel
+                .write_register(address, value, frame_step),
+            // NR50 - Master volume
+            0xFF24 => {
+                self.vin_panning = value & 0b1000_1000;
+                self.left_volume = ((value >> 4) & 0b111) + 1;
+                self.right_volume = (value & 0b111) + 1;
+            }
+            // NR51 - Sound panning
+            0xFF25 => self.pan_options = PanRegister::from_bits_truncate(value),
+            // NR52 - Master control
+            0xFF26 => {
+                let on = value & 0b1000_0000 > 0;
+                if self.on && !on {
+                    self.turn_off();
+                } else {
+                    self.on = on;
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::*;
+use std::{
+    cell::RefCell,
+    io::{self, Seek, SeekFrom, Write},
+    rc::Rc,
+};
+
+/// Sample format of WAV files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavFormat {
+    /// 16-bit signed integer PCM
+    PCM16,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+impl WavFormat {
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            WavFormat::PCM16 => 2,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+impl std::str::FromStr for WavFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "PCM16" => Ok(WavFormat::PCM16),
+            "Float32" => Ok(WavFormat::Float32),
+            _ => Err(format!("Unknown WAV format {name}")),
+        }
+    }
+}
+
+/// Writes samples into a WAV file. The sizes in the header are updated every few writes,
+/// so an unfinished file stays playable up to the last update
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    format: WavFormat,
+    /// Bytes of sample data written after the header
+    data_size: u32,
+    /// Writes since the sizes in the header were last updated
+    pending_writes: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Size of the RIFF, format and data chunk headers
+    const HEADER_SIZE: u32 = 44;
+    /// Amount of writes after which the sizes in the header are updated
+    const HEADER_UPDATE_INTERVAL: u32 = 64;
+
+    /// Writes the header of a WAV file with given amount of interleaved channels
+    pub fn new(
+        mut writer: W,
+        sample_rate: u32,
+        channels: u16,
+        format: WavFormat,
+    ) -> io::Result<Self> {
+        let block_align = channels * format.bytes_per_sample();
+        let format_tag: u16 = match format {
+            WavFormat::PCM16 => 1,
+            WavFormat::Float32 => 3,
+        };
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(Self::HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&format_tag.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(format.bytes_per_sample() * 8).to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            format,
+            data_size: 0,
+            pending_writes: 0,
+        })
+    }
+
+    /// Appends interleaved samples between -1.0 and 1.0.
+    /// Fails without writing if the file would grow past the 4 GiB limit of WAV files
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * 4);
+        for &sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            match self.format {
+                WavFormat::PCM16 => {
+                    bytes.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes())
+                }
+                WavFormat::Float32 => bytes.extend_from_slice(&sample.to_le_bytes()),
+            }
+        }
+        // The RIFF chunk size includes the headers after it, so it has to fit as well
+        let data_size = u32::try_from(bytes.len())
+            .ok()
+            .and_then(|size| self.data_size.checked_add(size))
+            .filter(|size| size.checked_add(Self::HEADER_SIZE - 8).is_some())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::FileTooLarge, "WAV file is limited to 4 GiB")
+            })?;
+        self.writer.write_all(&bytes)?;
+        self.data_size = data_size;
+
+        self.pending_writes += 1;
+        if self.pending_writes >= Self::HEADER_UPDATE_INTERVAL {
+            self.update_header()?;
+        }
+        Ok(())
+    }
+
+    /// Updates the sizes in the header and flushes the file, then returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.update_header()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the RIFF and data chunk sizes into the header, then returns to the end
+    fn update_header(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(Self::HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.pending_writes = 0;
+        Ok(())
+    }
+}
+
+/// Samples taken from the APU since the last time they were collected
+#[derive(Debug, Clone, Default)]
+pub struct RecordedAudio {
+    /// Final stereo mix with left and right samples interleaved
+    pub mix: Vec<f32>,
+    /// Mono output of each channel, taken after the DAC and before panning and volume.
+    /// Empty if the recording doesn't include stems
+    pub stems: [Vec<f32>; 4],
+}
+
+/// Shared buffer of audio recorded from the APU at its own sample rate,
+/// independent of audio playback. Clones share the same buffer,
+/// so a clone can be kept by the host to collect the samples
+#[derive(Clone)]
+pub struct AudioRecording {
+    samples: Rc<RefCell<RecordedAudio>>,
+    sample_rate: u32,
+    stems: bool,
+}
+
+impl AudioRecording {
+    /// Creates a recording at given sample rate,
+    /// which also records each channel separately if `stems` is set
+    pub fn new(sample_rate: u32, stems: bool) -> Self {
+        Self {
+            samples: Rc::default(),
+            sample_rate,
+            stems,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// If each channel is also recorded separately
+    pub fn has_stems(&self) -> bool {
+        self.stems
+    }
+
+    /// Removes and returns all recorded samples
+    pub fn take(&self) -> RecordedAudio {
+        std::mem::take(&mut self.samples.borrow_mut())
+    }
+}
+
+/// Band-limited outputs feeding an audio recording
+pub(crate) struct Recorder {
+    recording: AudioRecording,
+    mix: [BlipBuffer; 2],
+    stems: Option<[BlipBuffer; 4]>,
+    hpf_capacitors: [f32; 2],
+    hpf_capacitor_charge_factor: f32,
+}
+
+impl Recorder {
+    pub fn new(recording: AudioRecording) -> Self {
+        let sample_rate = recording.sample_rate;
+        let blip = || BlipBuffer::new(APU::CLOCK_SPEED, sample_rate);
+        Self {
+            mix: [blip(), blip()],
+            stems: recording.stems.then(|| std::array::from_fn(|_| blip())),
+            hpf_capacitors: [0.0; 2],
+            hpf_capacitor_charge_factor: APU::capacitor_charge_factor(sample_rate),
+            recording,
+        }
+    }
+
+    /// Sets the amplitudes of the mix and the channels at given T-cycle of the current frame
+    pub fn set_levels(&mut self, time: u32, levels: [f32; 2], samples: [f32; 4]) {
+        for (output, level) in self.mix.iter_mut().zip(levels) {
+            output.set_level(time, level);
+        }
+        if let Some(stems) = &mut self.stems {
+            for (output, sample) in stems.iter_mut().zip(samples) {
+                output.set_level(time, sample);
+            }
+        }
+    }
+
+    /// Moves the samples finished during the frame into the recording
+    pub fn end_frame(&mut self, time: u32) {
+        let mut recorded = self.recording.samples.borrow_mut();
+        let [left, right] = &mut self.mix;
+        left.end_frame(time);
+        right.end_frame(time);
+        while left.samples_available() > 0 {
+            for (output, capacitor) in [&mut *left, &mut *right]
+                .into_iter()
+                .zip(&mut self.hpf_capacitors)
+            {
+                let sample = output.read_sample();
+                recorded.mix.push(APU::high_pass(
+                    sample,
+                    capacitor,
+                    self.hpf_capacitor_charge_factor,
+                ));
+            }
+        }
+        if let Some(stems) = &mut self.stems {
+            for (output, stem) in stems.iter_mut().zip(&mut recorded.stems) {
+                output.end_frame(time);
+                while output.samples_available() > 0 {
+                    stem.push(output.read_sample());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use super::*;
+
+/// Memory buses that OAM DMA and the CPU can conflict on
+#[derive(PartialEq)]
+enum Bus {
+    /// Cartridge and work RAM
+    External,
+    /// VRAM
+    Video,
+}
+
+impl Bus {
+    /// Returns the bus given address is accessed through, if it's one DMA can use
+    fn of(address: u16) -> Option<Self> {
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xFDFF => Some(Bus::External),
+            0x8000..=0x9FFF => Some(Bus::Video),
+            _ => None,
+        }
+    }
+}
+
+/// OAM DMA transfer, which copies 160 bytes to OAM one byte per M-cycle
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub(crate) struct OAMDMA {
+    /// Source address of the transfer
+    source: u16,
+    /// Index of the next byte to copy
+    index: u8,
+    /// If a transfer is ongoing
+    active: bool,
+    /// Source address of a requested transfer, and M-cycles before it starts
+    pending: Option<(u16, u8)>,
+}
+
+impl OAMDMA {
+    /// Amount of bytes copied by one transfer
+    const LENGTH: u8 = 160;
+    /// M-cycles between writing to DMA register and the first byte being copied
+    const STARTUP_DELAY: u8 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            index: 0,
+            active: false,
+            pending: None,
+        }
+    }
+
+    /// Requests a new transfer from given page, which replaces the ongoing transfer once it starts
+    pub fn request(&mut self, page: u8) {
+        // Sources above $DFFF read from work RAM instead of echo RAM and I/O
+        let page = if page >= 0xE0 { page - 0x20 } else { page };
+        self.pending = Some((u16::from(page) << 8, Self::STARTUP_DELAY));
+    }
+
+    /// Address of the byte DMA is currently reading from
+    fn current_address(&self) -> u16 {
+        self.source + u16::from(self.index.saturating_sub(1))
+    }
+}
+
+/// VRAM DMA of CGB, which copies blocks of 16 bytes to VRAM
+/// either all at once or one block per HBlank
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub(crate) struct HDMA {
+    /// Source address of the next block (HDMA1, HDMA2)
+    source: u16,
+    /// Destination address of the next block in VRAM (HDMA3, HDMA4)
+    destination: u16,
+    /// Blocks left to copy minus one, which wraps to $7F once the transfer is done
+    length: u8,
+    /// If an HBlank transfer is ongoing
+    hblank_active: bool,
+    /// If a general purpose transfer has been started and is waiting to be copied
+    general_pending: bool,
+}
+
+impl HDMA {
+    /// Amount of bytes in one block
+    const BLOCK_LENGTH: u16 = 16;
+    /// M-cycles the CPU is paused for while one block is copied at normal speed
+    const BLOCK_CYCLES: u32 = 8;
+
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            destination: 0x8000,
+            length: 0x7F,
+            hblank_active: false,
+            general_pending: false,
+        }
+    }
+}
+
+impl MemoryAccess for HDMA {
+    fn mem_read(&self, address: u16) -> u8 {
+        match address {
+            // HDMA5: remaining length, where bit 7 is cleared while an HBlank transfer is ongoing
+            0xFF55 if self.hblank_active => self.length,
+            0xFF55 => 0b1000_0000 | self.length,
+            // Address registers can't be read
+            _ => 0xFF,
+        }
+    }
+    fn mem_write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF51 => self.source = (u16::from(value) << 8) | (self.source & 0xFF),
+            0xFF52 => self.source = (self.source & 0xFF00) | u16::from(value & 0xF0),
+            0xFF53 => {
+                self.destination =
+                    0x8000 | (u16::from(value & 0x1F) << 8) | (self.destination & 0xFF)
+            }
+            0xFF54 => self.destination = (self.destination & 0xFF00) | u16::from(value & 0xF0),
+            // HDMA5: writing bit 7 cleared stops an ongoing HBlank transfer
+            0xFF55 if self.hblank_active && value & 0x80 == 0 => self.hblank_active = false,
+            0xFF55 => {
+                self.length = value & 0x7F;
+                if value & 0x80 > 0 {
+                    self.hblank_active = true;
+                } else {
+                    self.general_pending = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl CPU {
+    /// Copies blocks requested through VRAM DMA, pausing the CPU while they're copied.
+    /// Called in between instructions
+    pub(crate) fn run_hdma(&mut self) {
+        let hblank_started = self.ppu.hblank_started;
+        self.ppu.hblank_started = false;
+        if self.hdma.general_pending {
+            self.hdma.general_pending = false;
+            for _ in 0..=self.hdma.length {
+                self.copy_hdma_block();
+            }
+        } else if self.hdma.hblank_active && hblank_started {
+            self.copy_hdma_block();
+        }
+    }
+
+    /// Writes to a VRAM DMA register
+    pub(crate) fn write_hdma(&mut self, address: u16, value: u8) {
+        self.hdma.mem_write(address, value);
+        // HBlank transfer started while the LCD is off copies its first block right away
+        if address == 0xFF55 && self.hdma.hblank_active && self.ppu.state == PPUState::Disabled {
+            self.ppu.hblank_started = true;
+        }
+    }
+
+    /// Copies one block of VRAM DMA
+    fn copy_hdma_block(&mut self) {
+        for _ in 0..HDMA::BLOCK_LENGTH {
+            let value = self.dma_read(self.hdma.source);
+            self.ppu.write_vram(self.hdma.destination, value);
+            self.hdma.source = self.hdma.source.wrapping_add(1);
+            // Destination wraps around within VRAM
+            self.hdma.destination = 0x8000 | (self.hdma.destination.wrapping_add(1) & 0x1FFF);
+        }
+        self.hdma.length = self.hdma.length.wrapping_sub(1) & 0x7F;
+        if self.hdma.length == 0x7F {
+            self.hdma.hblank_active = false;
+        }
+        // Copying takes the same time at both speeds
+        let cycles = HDMA::BLOCK_CYCLES << u32::from(self.double_speed);
+        self.cycle(cycles);
+    }
+
+    /// Cycles OAM DMA forward by one M-cycle
+    pub(crate) fn cycle_oam_dma(&mut self) {
+        if self.ppu.oam_dma_request {
+            self.ppu.oam_dma_request = false;
+            self.dma.request(self.ppu.oam_dma_source);
+        }
+
+        if self.dma.active && self.dma.index == OAMDMA::LENGTH {
+            self.dma.active = false;
+        }
+        match self.dma.pending {
+            Some((source, 0)) => {
+                self.dma.pending = None;
+                self.dma.source = source;
+                self.dma.index = 0;
+                self.dma.active = true;
+            }
+            Some((source, delay)) => self.dma.pending = Some((source, delay - 1)),
+            None => {}
+        }
+
+        if self.dma.active {
+            let address = self.dma.source + u16::from(self.dma.index);
+            let value = self.dma_read(address);
+            self.ppu.oam.write(u16::from(self.dma.index), value);
+            self.dma.index += 1;
+        }
+        self.ppu.oam_dma_active = self.dma.active;
+    }
+
+    /// Reads a byte for OAM DMA, which isn't affected by the PPU blocking CPU access
+    fn dma_read(&self, address: u16) -> u8 {
+        match address {
+            0x8000..=0x9FFF => self.ppu.read_vram(address),
+            _ => self.mem.mem_read(address),
+        }
+    }
+
+    /// Returns if an access to given address by the CPU conflicts with OAM DMA.
+    /// The CPU can't use the bus DMA is reading from, but HRAM and I/O are always available
+    pub(crate) fn dma_conflict(&self, address: u16) -> bool {
+        self.dma.active && Bus::of(address) == Bus::of(self.dma.source)
+    }
+
+    /// Returns the value the CPU reads when its read conflicts with OAM DMA,
+    /// which is the byte DMA is reading
+    pub(crate) fn dma_conflict_read(&self) -> u8 {
+        self.dma_read(self.dma.current_address())
+    }
+}
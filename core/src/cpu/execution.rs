@@ -1,26 +1,49 @@
 use super::*;
 
 impl CPU {
+    /// M-cycles the CPU is paused for after switching speed
+    const SPEED_SWITCH_CYCLES: u32 = 2050;
+
     /// Emulates the Game Boy (apart from instructions) for given amount of M-cycles
     pub(crate) fn cycle(&mut self, cycles: u32) {
-        self.cycle_counter += cycles;
-        // Rest of the system runs on T-cycles, which are 1/4 of an M-cycle
-        for _ in 0..(4 * cycles) {
-            // Check if OAM DMA should be started
-            if self.ppu.oam_dma_request {
-                self.ppu.oam_dma_request = false;
-                self.oam_dma(self.ppu.oam_dma_source);
+        for _ in 0..cycles {
+            // OAM DMA copies one byte per M-cycle
+            self.cycle_oam_dma();
+            // Rest of the system runs on T-cycles, which are 1/4 of an M-cycle.
+            // In double speed mode only the timer and serial port keep up with the CPU,
+            // so the rest of the system skips every other T-cycle
+            for t in 0..4 {
+                self.cycle_t(!self.double_speed || t % 2 == 0);
             }
-            // Cycle PPU
+            self.cycle_counter += if self.double_speed { 2 } else { 4 };
+        }
+    }
+
+    /// Emulates the Game Boy (apart from instructions and OAM DMA) for one T-cycle.
+    /// PPU, APU and cartridge hardware are only cycled if `system_tick` is set
+    fn cycle_t(&mut self, system_tick: bool) {
+        // Cycle PPU
+        if system_tick {
             self.ppu.cycle();
             self.request_interrupt(self.ppu.interrupt_request);
-            // Cycle timer
-            self.timer.cycle();
-            if self.timer.request_interrupt {
-                self.request_interrupt(InterruptFlag::TIMER);
-            }
-            // Cycle APU based on timer state
-            self.apu.cycle(self.timer.div);
+        }
+        // Cycle timer
+        self.timer.cycle();
+        if self.timer.request_interrupt {
+            self.request_interrupt(InterruptFlag::TIMER);
+        }
+        // Cycle APU and serial port based on timer state
+        if system_tick {
+            let div_apu_tick = std::mem::take(&mut self.timer.div_apu_tick);
+            self.apu.cycle(div_apu_tick);
+        }
+        self.serial.cycle(self.timer.div);
+        if self.serial.request_interrupt {
+            self.request_interrupt(InterruptFlag::SERIAL);
+        }
+        // Cycle cartridge hardware
+        if system_tick {
+            self.mem.cycle();
         }
     }
 
@@ -29,27 +52,71 @@ impl CPU {
     pub(crate) fn run_instruction(&mut self) {
         let start_active = self.ppu.state == PPUState::Active;
         let start_vblank = self.ppu.mode == PPUMode::VBlank;
+        if self.stopped {
+            // Whole system is stopped, so time passes without anything being cycled.
+            // Pressing a button in the selected input lines resumes it
+            self.cycle_counter += 4;
+            if self.input.line_low() {
+                self.stopped = false;
+            }
+            return;
+        }
+
+        // IME set by EI takes effect after the following instruction
+        let enable_ime = self.istate.ime_scheduled;
         // Check for possible interrupt requests
-        self.check_for_interrupt();
+        if self.lockup.is_none() {
+            self.check_for_interrupt();
+        }
 
-        if self.halt {
+        if self.lockup.is_some() {
+            // Locked up CPU ignores even interrupts, but the rest of the system keeps running
+            self.cycle(1);
+        } else if self.halt {
             // CPU doesn't execute anything when HALTed,
             // so just cycle the system forward until HALT is lifted
             self.cycle(1);
         } else {
             self.run_opcode();
         }
+        self.run_hdma();
+        if enable_ime && self.istate.ime_scheduled {
+            self.istate.ime_scheduled = false;
+            self.istate.ime = true;
+        }
 
         // Increment frame counter if system hit VBlank during execution or got disabled
         let end_disabled = self.ppu.state == PPUState::Disabled;
         let end_vblank = self.ppu.mode == PPUMode::VBlank;
         if (!start_vblank && end_vblank) || (start_active && end_disabled) {
             self.frame_counter = self.frame_counter.wrapping_add(1);
+            if let Some(sgb) = &mut self.sgb {
+                sgb.frame_finished(&self.ppu.display);
+            }
         }
     }
 
     fn run_opcode(&mut self) {
         let opcode = self.read(self.reg.pc);
+        // Opcodes without an instruction hang the CPU
+        if matches!(
+            opcode,
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD
+        ) {
+            log::error!(
+                "CPU locked up by illegal opcode {:#04X} at {:#06X}",
+                opcode,
+                self.reg.pc
+            );
+            self.lockup = Some(self.reg.pc);
+            self.cycle(1);
+            return;
+        }
+        // After the HALT bug, the byte after the opcode is read from the same address again
+        if self.halt_bug {
+            self.halt_bug = false;
+            self.reg.pc = self.reg.pc.wrapping_sub(1);
+        }
         let mut increment_pc = true;
         {
             match opcode {
@@ -61,9 +128,7 @@ impl CPU {
                             // NOP
                             0x00 => {}
                             // STOP
-                            0x10 => {
-                                log::warn!("Tried to run STOP instruction");
-                            }
+                            0x10 => self.stop(),
                             // JR
                             _ => {
                                 let step = self.read_operand() as i8;
@@ -323,7 +388,13 @@ impl CPU {
                 }
                 // HALT
                 0x76 => {
-                    self.halt = true;
+                    let interrupt_pending = self.istate.ie.intersects(self.istate.iflag);
+                    if !self.istate.ime && interrupt_pending {
+                        // HALT exits right away without incrementing program counter
+                        self.halt_bug = true;
+                    } else {
+                        self.halt = true;
+                    }
                 }
                 0xC0..=0xFF => {
                     // Mask out the first nibble for easier pattern matching
@@ -333,6 +404,8 @@ impl CPU {
                             // DI
                             if opcode == 0xF3 {
                                 self.istate.ime = false;
+                                // DI right after EI cancels it
+                                self.istate.ime_scheduled = false;
                             }
                             // LD
                             else if opcode & 0xF0 >= 0xE0 {
@@ -519,7 +592,7 @@ impl CPU {
                         0xB => {
                             // EI
                             if opcode == 0xFB {
-                                self.istate.ime = true;
+                                self.istate.ime_scheduled = true;
                             }
                             // 0xCB 16-bit opcodes
                             else {
@@ -533,12 +606,52 @@ impl CPU {
         }
 
         if increment_pc {
-            self.reg.pc += 1;
+            self.reg.pc = self.reg.pc.wrapping_add(1);
         }
         // Every instruction takes at least one M-cycle to execute
         self.cycle(1);
     }
 
+    /// Switches between normal and CGB double speed
+    pub(crate) fn set_double_speed(&mut self, double_speed: bool) {
+        self.double_speed = double_speed;
+        // Divider runs twice as fast in double speed mode,
+        // so DIV-APU is clocked by the next bit instead
+        self.timer.div_apu_bit = if double_speed { 13 } else { 12 };
+    }
+
+    /// Executes STOP, which enters low-power mode until a button is pressed
+    fn stop(&mut self) {
+        // In CGB mode, STOP switches the CPU speed if requested through KEY1
+        if self.speed_switch_armed {
+            self.speed_switch_armed = false;
+            self.set_double_speed(!self.double_speed);
+            self.reg.pc = self.reg.pc.wrapping_add(1);
+            self.timer.mem_write(0xFF04, 0);
+            // CPU is paused while the clock settles
+            self.cycle(Self::SPEED_SWITCH_CYCLES);
+            return;
+        }
+        let interrupt_pending = self.istate.ie.intersects(self.istate.iflag);
+        // STOP is normally followed by a byte that's skipped
+        if !interrupt_pending {
+            self.reg.pc = self.reg.pc.wrapping_add(1);
+        }
+        // If a button is already held, STOP doesn't enter low-power mode
+        if self.input.line_low() {
+            if !interrupt_pending {
+                self.halt = true;
+            }
+            return;
+        }
+        // Divider is reset when the system is stopped
+        self.timer.mem_write(0xFF04, 0);
+        self.stopped = true;
+        // LCD shows white while the system is stopped
+        self.ppu.blank_display();
+        self.frame_counter = self.frame_counter.wrapping_add(1);
+    }
+
     /// Executes the 16-bit long arithmetic opcodes that start with 0xCB
     fn arithmetic(&mut self) {
         let opcode = self.read_operand();
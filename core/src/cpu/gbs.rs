@@ -0,0 +1,85 @@
+use super::*;
+
+impl CPU {
+    /// Creates CPU that plays the songs of a GBS music file instead of running a cartridge.
+    /// The boot ROM in the config is ignored, and the first song of the file starts playing
+    pub fn from_gbs(
+        file: Vec<u8>,
+        config: HardwareConfig,
+    ) -> Result<Self, MemoryInitializationError> {
+        let header = GBSHeader::parse(&file)?;
+        let mem = Memory::for_gbs(header.build_rom(&file), header.title.clone());
+        let config = HardwareConfig {
+            boot_rom: None,
+            ..config
+        };
+        let mut cpu = Self::with_memory(mem, 0, config)?;
+        let first_song = header.first_song;
+        cpu.gbs = Some(GBSPlayer { header, track: 0 });
+        cpu.select_gbs_track(first_song);
+        Ok(cpu)
+    }
+
+    /// Returns the header of the GBS file being played, if any
+    pub fn get_gbs_header(&self) -> Option<&GBSHeader> {
+        self.gbs.as_ref().map(|gbs| &gbs.header)
+    }
+
+    /// Returns the song of the GBS file being played, counting from 0
+    pub fn get_gbs_track(&self) -> Option<u8> {
+        self.gbs.as_ref().map(|gbs| gbs.track)
+    }
+
+    /// Starts playing given song of the GBS file, counting from 0.
+    /// Memory, sound and the timer are reset before calling the init routine of the song
+    pub fn select_gbs_track(&mut self, track: u8) {
+        let Some(gbs) = &mut self.gbs else {
+            log::warn!("Tried to select a track without a GBS file loaded");
+            return;
+        };
+        if track >= gbs.header.song_count {
+            log::warn!(
+                "Tried to select track {track}, but the GBS file only has {}",
+                gbs.header.song_count
+            );
+            return;
+        }
+        gbs.track = track;
+        let header = gbs.header.clone();
+
+        self.mem.clear_ram();
+        self.write(0x2000, 1);
+        // Sound is reset by turning the APU off and on
+        self.write(0xFF26, 0x00);
+        self.write(0xFF26, 0x80);
+        self.write(0xFF25, 0xFF);
+        self.write(0xFF24, 0x77);
+        // Bit 7 of TAC asks for double speed, which only CGB supports
+        let double_speed = header.timer_control & 0x80 > 0;
+        if double_speed && !self.ppu.cgb_hardware {
+            log::warn!("GBS file asks for CGB double speed, playing at normal speed instead");
+        }
+        self.set_double_speed(double_speed && self.ppu.cgb_hardware);
+        self.speed_switch_armed = false;
+        self.write(0xFF06, header.timer_modulo);
+        self.write(0xFF07, header.timer_control & 0b111);
+        self.write(0xFF05, header.timer_modulo);
+        let interrupt = if header.uses_timer() {
+            InterruptFlag::TIMER
+        } else {
+            InterruptFlag::VBLANK
+        };
+        self.write(0xFFFF, interrupt.bits());
+        self.write(0xFF0F, 0x00);
+        self.istate.ime = false;
+        self.istate.ime_scheduled = false;
+        self.halt = false;
+        self.halt_bug = false;
+        self.stopped = false;
+        self.lockup = None;
+
+        self.reg.a = track;
+        self.reg.sp = header.stack_pointer;
+        self.reg.pc = ENTRY;
+    }
+}
@@ -17,6 +17,8 @@ bitflags! {
 pub(crate) struct InterruptState {
     /// Master interrupt enable
     pub ime: bool,
+    /// If IME is set after the next instruction, which is how EI is delayed
+    pub ime_scheduled: bool,
     /// Interrupt enable flag
     pub ie: InterruptFlag,
     /// Interrupt request flag
@@ -27,6 +29,7 @@ impl InterruptState {
     pub fn new() -> Self {
         Self {
             ime: false,
+            ime_scheduled: false,
             iflag: InterruptFlag::from_bits_truncate(0),
             ie: InterruptFlag::from_bits_truncate(0),
         }
@@ -70,6 +73,11 @@ impl CPU {
         };
         // CPU waits for 2 M-cycles (for some reason)
         self.cycle(2);
+        // After the HALT bug caused by EI right before HALT, the interrupt returns to the HALT
+        if self.halt_bug {
+            self.halt_bug = false;
+            self.reg.pc = self.reg.pc.wrapping_sub(1);
+        }
         // Move program counter to interrupt address
         self.push(self.reg.pc);
         self.reg.pc = address;
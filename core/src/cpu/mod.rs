@@ -1,11 +1,26 @@
+mod dma;
 mod execution;
+mod gbs;
 mod interrupts;
 mod readwrite;
 
 use super::*;
+use dma::*;
 pub(crate) use interrupts::*;
 pub(crate) use readwrite::*;
 
+/// Options for the emulated hardware
+#[derive(Clone, Default)]
+pub struct HardwareConfig {
+    /// 256-byte boot ROM to run before the cartridge.
+    /// If not given, the system starts from the state the boot ROM would leave it in
+    pub boot_rom: Option<Vec<u8>>,
+    /// Hardware model to emulate.
+    /// If not given, the model is chosen based on if the cartridge supports Game Boy Color
+    /// or Super Game Boy
+    pub model: Option<Model>,
+}
+
 /// The main processing unit
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Deserialize, Serialize)]
@@ -13,34 +28,130 @@ pub struct CPU {
     mem: Memory,
     reg: Registers,
     ppu: PPU,
+    dma: OAMDMA,
+    hdma: HDMA,
     apu: APU,
     timer: Timer,
+    serial: Serial,
     input: InputReg,
+    /// Super Game Boy features, if running an SGB cartridge on SGB
+    sgb: Option<SGB>,
     istate: InterruptState,
     halt: bool,
+    /// If HALT was exited right away, which makes the CPU read the next byte twice
+    halt_bug: bool,
+    /// If in the low-power mode entered with STOP, which stops the system until a button is pressed
+    stopped: bool,
+    /// Address of the illegal opcode that locked up the CPU, if any
+    lockup: Option<u16>,
+    /// If running a Game Boy Color cartridge on CGB, enabling its extra hardware
+    cgb: bool,
+    /// If the CPU runs at twice the normal speed, which is only possible in CGB mode
+    double_speed: bool,
+    /// If the next STOP switches the CPU speed (KEY1 bit 0)
+    speed_switch_armed: bool,
     pub frame_counter: u8,
+    /// Elapsed T-cycles at normal speed, so double speed M-cycles count as two
     cycle_counter: u32,
+    /// GBS music file being played instead of a cartridge, if any
+    gbs: Option<GBSPlayer>,
 }
 
 impl CPU {
     pub fn new(rom_file: Vec<u8>) -> Result<Self, MemoryInitializationError> {
-        let mem = Memory::new(rom_file)?;
-        Ok(Self {
+        Self::with_config(rom_file, HardwareConfig::default())
+    }
+
+    /// Creates CPU with given hardware options
+    pub fn with_config(
+        rom_file: Vec<u8>,
+        config: HardwareConfig,
+    ) -> Result<Self, MemoryInitializationError> {
+        let header_checksum = rom_file.get(0x014D).copied().unwrap_or_default();
+        Self::with_memory(Memory::new(rom_file)?, header_checksum, config)
+    }
+
+    /// Creates CPU with given hardware options around already initialized memory
+    fn with_memory(
+        mem: Memory,
+        header_checksum: u8,
+        config: HardwareConfig,
+    ) -> Result<Self, MemoryInitializationError> {
+        let model = config
+            .model
+            .unwrap_or_else(|| Model::for_cartridge(&mem.info));
+        // Color features are only enabled for cartridges that support them
+        let cgb = model == Model::CGB && mem.info.cgb_support;
+        let sgb =
+            (matches!(model, Model::SGB | Model::SGB2) && mem.info.sgb_support).then(SGB::new);
+        let mut cpu = Self {
             mem,
-            reg: Registers::new(),
+            reg: Registers::power_on(),
             ppu: PPU::new(),
+            dma: OAMDMA::new(),
+            hdma: HDMA::new(),
             apu: APU::new(),
             timer: Timer::new(),
+            serial: Serial::new(),
             input: InputReg::new(),
+            sgb,
             istate: InterruptState::new(),
             halt: false,
+            halt_bug: false,
+            stopped: false,
+            lockup: None,
+            cgb,
+            double_speed: false,
+            speed_switch_armed: false,
             frame_counter: 0,
             cycle_counter: 0,
-        })
+            gbs: None,
+        };
+        cpu.ppu.cgb = cgb;
+        cpu.ppu.cgb_hardware = model == Model::CGB;
+        cpu.apu.cgb = model == Model::CGB;
+        if let Some(boot_rom) = config.boot_rom {
+            cpu.mem.set_boot_rom(boot_rom)?;
+            // Boot ROM starts from zeroed registers and turns the LCD on by itself
+            cpu.ppu.mem_write(0xFF40, 0x00);
+        } else {
+            cpu.skip_boot(model.post_boot_state(header_checksum, cgb));
+        }
+        Ok(cpu)
+    }
+
+    /// Sets the system to the state the boot ROM would leave it in
+    fn skip_boot(&mut self, state: PostBootState) {
+        self.reg = state.registers;
+        self.write(0xFF00, state.p1);
+        for &(address, value) in state.io {
+            self.write(address, value);
+        }
+        // The chime has faded out, but channel 1 is still on with the period of its last note
+        if state.chime {
+            let channel = &mut self.apu.square_channel_1;
+            channel.channel_on = true;
+            channel.period = 0x7C1;
+            channel.envelope.volume = 0;
+        }
+        self.timer.div = state.div;
+        // OAM DMA register reads $FF, but writing it would start a transfer
+        self.ppu.oam_dma_source = 0xFF;
+        self.ppu.ly = state.ly;
+        self.ppu.lx = state.lx;
+        self.ppu.mode = PPUMode::VBlank;
+        if self.cgb {
+            self.ppu.color_palettes.clear_bg();
+        }
     }
 
-    /// Writes ROM to memory: used to rewrite ROM after deserialization
+    /// Writes ROM to memory: used to rewrite ROM after deserialization.
+    /// When playing a GBS file, the file itself should be given
     pub fn set_rom(&mut self, rom: Vec<u8>) {
+        let rom = match &self.gbs {
+            Some(gbs) => gbs.header.build_rom(&rom),
+            None => rom,
+        };
         self.mem.set_rom(rom);
     }
 
@@ -54,6 +165,13 @@ impl CPU {
         self.mem.get_ram()
     }
 
+    /// Advances the cartridge real-time clock to given Unix timestamp in seconds.
+    /// Should be called after loading RAM and periodically during execution,
+    /// so the clock keeps up with the host time while emulation isn't running
+    pub fn sync_rtc(&mut self, timestamp: u64) {
+        self.mem.sync_rtc(timestamp);
+    }
+
     /// Initializes a ring buffer for audio playback and returns its consumer.
     /// Remember to set sample rate using set_audio_sample_rate
     pub fn init_audio_buffer(
@@ -70,11 +188,69 @@ impl CPU {
         self.apu.set_sample_rate(sample_rate);
     }
 
+    /// Initializes a separate ring buffer for each sound channel and returns their consumers,
+    /// in the order of `AudioChannel::ALL`. Each buffer holds the mono DAC output of its channel
+    /// before panning, volume and muting, at given sample rate.
+    /// The sample rate isn't changed by set_audio_sample_rate
+    pub fn init_channel_audio_buffers(
+        &mut self,
+        sample_capacity: usize,
+        sample_rate: u32,
+    ) -> [AudioBufferConsumer; 4] {
+        self.apu.init_channel_buffers(sample_capacity, sample_rate)
+    }
+
+    /// Leaves a sound channel out of the audio output or adds it back
+    pub fn set_audio_channel_muted(&mut self, channel: AudioChannel, muted: bool) {
+        self.apu.set_muted(channel, muted);
+    }
+
+    /// Plays a sound channel alone in the audio output, together with the other soloed channels.
+    /// Muted channels stay silent even when soloed
+    pub fn set_audio_channel_solo(&mut self, channel: AudioChannel, solo: bool) {
+        self.apu.set_soloed(channel, solo);
+    }
+
+    /// Starts recording audio into given recording, which is filled as the CPU runs regardless
+    /// of audio playback. Keep a clone of the recording to collect the samples
+    pub fn start_audio_recording(&mut self, recording: AudioRecording) {
+        self.apu.start_recording(recording);
+    }
+
+    /// Stops adding samples to the audio recording
+    pub fn stop_audio_recording(&mut self) {
+        self.apu.stop_recording();
+    }
+
     /// Returns the latest fully drawn display buffer for rendering
     pub fn get_display_buffer(&self) -> &DisplayBuffer {
         &self.ppu.display
     }
 
+    /// Returns the latest fully drawn color display buffer for rendering,
+    /// if the cartridge is running in CGB mode
+    pub fn get_color_display_buffer(&self) -> Option<&ColorDisplayBuffer> {
+        self.cgb.then(|| &*self.ppu.color_display)
+    }
+
+    /// Returns the latest Super Game Boy picture with the colored screen inside the border,
+    /// if the cartridge is running on SGB
+    pub fn get_sgb_frame_buffer(&self) -> Option<&SGBFrameBuffer> {
+        self.sgb.as_ref().map(|sgb| sgb.frame())
+    }
+
+    /// Returns the address of the illegal opcode the CPU locked up on, if any.
+    /// A locked up CPU doesn't execute anything until the system is reset
+    pub fn get_lockup_address(&self) -> Option<u16> {
+        self.lockup
+    }
+
+    /// Returns the amount of VRAM and OAM accesses dropped because the PPU was using the memory.
+    /// Useful for finding code that accesses them at the wrong time
+    pub fn get_blocked_accesses(&self) -> u32 {
+        self.ppu.blocked_accesses()
+    }
+
     /// Updates input state
     pub fn update_input(&mut self, input: &InputFlag) {
         if self.input.update(*input) {
@@ -82,16 +258,47 @@ impl CPU {
         }
     }
 
+    /// Updates the tilt of cartridges with an accelerometer (MBC7).
+    /// Values are given in g, where 1.0 or -1.0 means the Game Boy is tilted fully on its side.
+    /// Positive X tilts the right side of the Game Boy down,
+    /// and positive Y tilts the bottom side down
+    pub fn update_tilt(&mut self, x: f32, y: f32) {
+        self.mem.set_tilt(x, y);
+    }
+
+    /// Connects the infrared port of HuC1 and HuC3 cartridges to given device.
+    /// By default the port never receives any light
+    pub fn set_infrared_port(&mut self, port: Box<dyn InfraredPort>) {
+        self.mem.set_infrared_port(port);
+    }
+
+    /// Sets the source of the images captured by the Game Boy Camera.
+    /// By default the camera sees a test pattern
+    pub fn set_camera_source(&mut self, source: Box<dyn CameraSource>) {
+        self.mem.set_camera_source(source);
+    }
+
+    /// Returns the tone currently played by the speaker of HuC3 cartridges, if any
+    pub fn get_cartridge_tone(&self) -> Option<u8> {
+        self.mem.get_tone()
+    }
+
+    /// Connects given device to the serial port.
+    /// By default the port is disconnected
+    pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.serial.set_device(device);
+    }
+
     /// Returns info about cartridge
     pub fn get_cartridge_info(&self) -> &CartridgeInfo {
         &self.mem.info
     }
 
-    const MS_PER_M_CYCLE: f32 = 0.0009536743;
+    const MS_PER_T_CYCLE: f32 = 0.0002384186;
 
     /// Runs Game Boy for given amount of milliseconds
     pub fn run(&mut self, millis: f32) {
-        let target_cycles = (millis / Self::MS_PER_M_CYCLE).floor() as u32;
+        let target_cycles = (millis / Self::MS_PER_T_CYCLE).floor() as u32;
         while self.cycle_counter < target_cycles {
             self.run_instruction();
         }
@@ -11,6 +11,9 @@ pub(crate) trait MemoryAccess {
 impl CPU {
     /// Reads from given memory address
     pub(crate) fn read(&self, address: u16) -> u8 {
+        if self.dma_conflict(address) {
+            return self.dma_conflict_read();
+        }
         match address {
             // ROM, external, work and echo RAM, high RAM
             0x0000..=0x7FFF | 0xA000..=0xFDFF | 0xFF80..=0xFFFE => self.mem.mem_read(address),
@@ -18,12 +21,31 @@ impl CPU {
             0x8000..=0x9FFF | 0xFE00..=0xFE9F | 0xFF40..=0xFF4B => self.ppu.mem_read(address),
             // Audio I/O registers
             0xFF10..=0xFF3F => self.apu.mem_read(address),
-            // Input register
-            0xFF00 => self.input.mem_read(address),
+            // Input register, which also reads the joypad index on SGB
+            0xFF00 => match &self.sgb {
+                Some(sgb) => sgb.read_joypad(&self.input),
+                None => self.input.mem_read(address),
+            },
+            // Serial transfer
+            0xFF01..=0xFF02 => self.serial.mem_read(address),
             // Timer control
             0xFF04..=0xFF07 => self.timer.mem_read(address),
             // Interrupt control (IF and IE)
             0xFF0F | 0xFFFF => self.istate.mem_read(address),
+            // Boot ROM disable
+            0xFF50 => self.mem.mem_read(address),
+            // CGB registers are only mapped in CGB mode
+            _ if !self.cgb => 0xFF,
+            // KEY1: current speed and speed switch request
+            0xFF4D => {
+                (u8::from(self.double_speed) << 7) | 0b0111_1110 | u8::from(self.speed_switch_armed)
+            }
+            // VRAM bank, color palettes, object priority mode
+            0xFF4F | 0xFF68..=0xFF6C => self.ppu.mem_read(address),
+            // VRAM DMA
+            0xFF51..=0xFF55 => self.hdma.mem_read(address),
+            // WRAM bank
+            0xFF70 => self.mem.mem_read(address),
             _ => 0xFF,
         }
     }
@@ -35,6 +57,10 @@ impl CPU {
 
     /// Writes to given memory address
     pub(crate) fn write(&mut self, address: u16, value: u8) {
+        // Bus used by OAM DMA ignores writes from the CPU
+        if self.dma_conflict(address) {
+            return;
+        }
         match address {
             // ROM, external, work and echo RAM, high RAM
             0x0000..=0x7FFF | 0xA000..=0xFDFF | 0xFF80..=0xFFFE => {
@@ -46,12 +72,31 @@ impl CPU {
             }
             // Audio I/O registers
             0xFF10..=0xFF3F => self.apu.mem_write(address, value),
-            // Input register
-            0xFF00 => self.input.mem_write(address, value),
+            // Input register, which is also used to send packets to SGB
+            0xFF00 => {
+                self.input.mem_write(address, value);
+                if let Some(sgb) = &mut self.sgb {
+                    sgb.write_joypad(value);
+                }
+            }
+            // Serial transfer
+            0xFF01..=0xFF02 => self.serial.mem_write(address, value),
             // Timer control
             0xFF04..=0xFF07 => self.timer.mem_write(address, value),
             // Interrupt control
             0xFF0F | 0xFFFF => self.istate.mem_write(address, value),
+            // Boot ROM disable
+            0xFF50 => self.mem.mem_write(address, value),
+            // CGB registers are only mapped in CGB mode
+            _ if !self.cgb => {}
+            // KEY1
+            0xFF4D => self.speed_switch_armed = value & 1 > 0,
+            // VRAM bank, color palettes, object priority mode
+            0xFF4F | 0xFF68..=0xFF6C => self.ppu.mem_write(address, value),
+            // VRAM DMA
+            0xFF51..=0xFF55 => self.write_hdma(address, value),
+            // WRAM bank
+            0xFF70 => self.mem.mem_write(address, value),
             _ => {}
         }
     }
@@ -90,17 +135,4 @@ impl CPU {
         self.write(self.reg.sp.wrapping_add(1), bytes[1]);
         self.write(self.reg.sp, bytes[0]);
     }
-
-    /// Performs an OAM DMA transfer, which copies memory from given source address to OAM
-    pub(crate) fn oam_dma(&mut self, address: u8) {
-        let source_address = (address as u16) * 0x100;
-        for sprite_index in 0..40 {
-            let sprite_address = source_address + (sprite_index * 4);
-            let mut data = [0u8; 4];
-            for i in 0..4u16 {
-                data[i as usize] = self.read(sprite_address + i);
-            }
-            self.ppu.oam.sprites[sprite_index as usize] = OAMSprite::from(data);
-        }
-    }
 }
@@ -0,0 +1,123 @@
+use super::*;
+
+/// Header of a GBS music file, which holds the sound driver and music data ripped from a game
+/// along with the addresses needed to play its songs
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GBSHeader {
+    /// Amount of songs in the file
+    pub song_count: u8,
+    /// Song to play first, counting from 0
+    pub first_song: u8,
+    /// Address the data after the header is loaded to
+    pub load_address: u16,
+    /// Routine that sets up the song given in register A
+    pub init_address: u16,
+    /// Routine that is called at a steady rate to play the song
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    /// Value of TMA when the play routine is called from the timer interrupt
+    pub timer_modulo: u8,
+    /// Value of TAC. If the timer is enabled, the play routine is called from the timer interrupt,
+    /// otherwise from VBlank
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+/// Address of the code that calls the init routine and then waits for interrupts
+pub(crate) const ENTRY: u16 = 0x0070;
+
+impl GBSHeader {
+    /// Size of the header, which the loaded data follows
+    pub const SIZE: usize = 0x70;
+
+    /// Reads the header at the start of a GBS file
+    pub fn parse(file: &[u8]) -> Result<Self, MemoryInitializationError> {
+        let invalid = |reason: &str| MemoryInitializationError {
+            error_type: MemoryInitializationErrorType::InvalidGBS(reason.to_string()),
+        };
+        if file.len() < Self::SIZE || &file[0..3] != b"GBS" {
+            return Err(invalid("file doesn't start with a GBS header"));
+        }
+        if file[3] != 1 {
+            return Err(invalid(&format!("version {} isn't supported", file[3])));
+        }
+        let word = |offset: usize| u16::from_le_bytes([file[offset], file[offset + 1]]);
+        let text = |offset: usize| {
+            let field = &file[offset..offset + 32];
+            let end = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+        let header = Self {
+            song_count: file[0x04],
+            first_song: file[0x05].saturating_sub(1),
+            load_address: word(0x06),
+            init_address: word(0x08),
+            play_address: word(0x0A),
+            stack_pointer: word(0x0C),
+            timer_modulo: file[0x0E],
+            timer_control: file[0x0F],
+            title: text(0x10),
+            author: text(0x30),
+            copyright: text(0x50),
+        };
+        if header.song_count == 0 {
+            return Err(invalid("file has no songs"));
+        }
+        if header.load_address < 0x0080 || header.load_address >= 0x8000 {
+            return Err(invalid(&format!(
+                "load address {:#06X} is outside the ROM or overlaps the player code",
+                header.load_address
+            )));
+        }
+        Ok(header)
+    }
+
+    /// If the play routine is called from the timer interrupt instead of VBlank
+    pub fn uses_timer(&self) -> bool {
+        self.timer_control & 0b100 > 0
+    }
+
+    /// Builds the cartridge ROM the GBS file is played from. The data is placed at its load
+    /// address, RST vectors jump to the same offsets from the load address,
+    /// interrupt handlers call the play routine, and the entry calls the init routine
+    /// and then halts until the next interrupt forever
+    pub(crate) fn build_rom(&self, file: &[u8]) -> Vec<u8> {
+        let data = file.get(Self::SIZE..).unwrap_or_default();
+        let load_address = usize::from(self.load_address);
+        let size = (load_address + data.len())
+            .next_multiple_of(0x4000)
+            .max(0x8000);
+        let mut rom = vec![0; size];
+        rom[load_address..load_address + data.len()].copy_from_slice(data);
+
+        // RST vectors: JP load address + vector
+        for vector in (0x00..0x40).step_by(8) {
+            let [low, high] = (self.load_address + vector as u16).to_le_bytes();
+            rom[vector..vector + 3].copy_from_slice(&[0xC3, low, high]);
+        }
+        // VBlank and timer: CALL play, RETI
+        let [play_low, play_high] = self.play_address.to_le_bytes();
+        for vector in [0x40, 0x50] {
+            rom[vector..vector + 4].copy_from_slice(&[0xCD, play_low, play_high, 0xD9]);
+        }
+        // STAT, serial and joypad: RETI
+        for vector in [0x48, 0x58, 0x60] {
+            rom[vector] = 0xD9;
+        }
+        // CALL init, EI, then HALT and JR back to it
+        let [init_low, init_high] = self.init_address.to_le_bytes();
+        let entry = usize::from(ENTRY);
+        rom[entry..entry + 7].copy_from_slice(&[0xCD, init_low, init_high, 0xFB, 0x76, 0x18, 0xFD]);
+        rom
+    }
+}
+
+/// State of a GBS file being played
+#[derive(Deserialize, Serialize)]
+pub(crate) struct GBSPlayer {
+    pub header: GBSHeader,
+    /// Song being played, counting from 0
+    pub track: u8,
+}
@@ -36,31 +36,40 @@ impl InputReg {
     pub fn update(&mut self, input: InputFlag) -> bool {
         // Get buttons that have been pressed (bit has changed from 1 to 0)
         let pressed = !input.bits() & self.flags.bits();
-        // Mask out pressed button based on selected input type
-        let send_interrupt = if self.select_button {
-            (pressed >> 4) > 0
-        } else if self.select_dpad {
-            pressed & 0x0F > 0
-        } else {
-            false
-        };
+        // Mask out pressed button based on selected input lines
+        let mut selected = 0;
+        if self.select_button {
+            selected |= 0xF0;
+        }
+        if self.select_dpad {
+            selected |= 0x0F;
+        }
+        let send_interrupt = pressed & selected > 0;
         // Return if interrupt should be sent
         self.flags = input;
         send_interrupt
     }
+
+    /// Returns if a button in the selected input lines is held down
+    pub fn line_low(&self) -> bool {
+        self.mem_read(0xFF00) & 0x0F != 0x0F
+    }
 }
 
 impl MemoryAccess for InputReg {
     fn mem_read(&self, _: u16) -> u8 {
-        let select_bits = ((self.select_button as u8) << 5) | ((self.select_dpad as u8) << 4);
+        // Select bits read back as written, where 0 selects the line. Unused bits read as 1
+        let select_bits =
+            0b1100_0000 | ((!self.select_button as u8) << 5) | ((!self.select_dpad as u8) << 4);
 
-        let input = if self.select_button {
-            (self.flags.bits() & 0xF0) >> 4
-        } else if self.select_dpad {
-            self.flags.bits() & 0x0F
-        } else {
-            0x0F
-        };
+        // With both lines selected, a button held on either line pulls the bit low
+        let mut input = 0x0F;
+        if self.select_button {
+            input &= (self.flags.bits() & 0xF0) >> 4;
+        }
+        if self.select_dpad {
+            input &= self.flags.bits() & 0x0F;
+        }
 
         select_bits | input
     }
@@ -4,21 +4,43 @@ use serde_big_array::BigArray;
 
 mod apu;
 mod cpu;
+mod gbs;
 mod input;
+mod link;
 mod memory;
+mod model;
 mod ppu;
+mod printer;
 mod registers;
+mod serial;
+mod sgb;
 mod timer;
 use apu::*;
 use cpu::*;
+use gbs::*;
 use input::*;
 use memory::*;
+use model::*;
 use ppu::*;
 use registers::*;
+use serial::*;
+use sgb::*;
 use timer::*;
 
-pub use apu::AudioBufferConsumer;
-pub use cpu::CPU;
+pub use apu::{
+    AudioBufferConsumer, AudioChannel, AudioRecording, RecordedAudio, WavFormat, WavWriter,
+};
+pub use cpu::{CPU, HardwareConfig};
+pub use gbs::GBSHeader;
 pub use input::InputFlag;
-pub use memory::{CartridgeInfo, MemoryInitializationError, MemoryInitializationErrorType};
-pub use ppu::{DISPLAY_BUFFER_SIZE, DisplayBuffer};
+pub use link::TcpLink;
+pub use memory::{
+    CAMERA_HEIGHT, CAMERA_WIDTH, CameraImage, CameraSource, CartridgeInfo, InfraredPort,
+    MemoryInitializationError, MemoryInitializationErrorType, NoInfrared, StaticImageCamera,
+    TestPatternCamera,
+};
+pub use model::Model;
+pub use ppu::{COLOR_DISPLAY_BUFFER_SIZE, ColorDisplayBuffer, DISPLAY_BUFFER_SIZE, DisplayBuffer};
+pub use printer::{PRINT_WIDTH, PrintQueue, PrintedImage, Printer};
+pub use serial::{Disconnected, SerialDevice, SerialLogger};
+pub use sgb::{SGB_FRAME_HEIGHT, SGB_FRAME_WIDTH, SGBFrameBuffer};
@@ -0,0 +1,184 @@
+use super::*;
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+/// Messages sent between linked instances, each followed by a data byte
+const TRANSFER: u8 = 0x01;
+const REPLY: u8 = 0x02;
+
+/// Link cable connecting two emulator instances over TCP.
+///
+/// The instance using the internal clock sends its byte and waits for the other instance to reply,
+/// which keeps the two instances in lockstep for every transferred byte.
+/// The other instance replies with its own byte when it's waiting for an external clock transfer,
+/// or with $FF when it isn't ready
+pub struct TcpLink {
+    stream: Option<TcpStream>,
+    /// Received bytes that don't form a complete message yet
+    buffer: Vec<u8>,
+    /// Byte sent by the other instance that hasn't been received by the Game Boy yet
+    pending_transfer: Option<u8>,
+    /// Amount of replies to transfers that already timed out, which are discarded when received
+    stale_replies: usize,
+}
+
+impl TcpLink {
+    /// How long a transfer waits for the other instance before giving up
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Waits for another instance to connect to given address
+    pub fn host(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        Self::from_stream(stream)
+    }
+
+    /// Connects to another instance hosting at given address
+    pub fn join(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::from_stream(TcpStream::connect(address)?)
+    }
+
+    fn from_stream(stream: TcpStream) -> io::Result<Self> {
+        // Messages are tiny and latency sensitive
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream: Some(stream),
+            buffer: Vec::new(),
+            pending_transfer: None,
+            stale_replies: 0,
+        })
+    }
+
+    /// If the other instance is still connected
+    pub fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn disconnect(&mut self, error: Option<io::Error>) {
+        match error {
+            Some(error) => log::warn!("Link cable disconnected: {error}"),
+            None => log::warn!("Link cable disconnected"),
+        }
+        self.stream = None;
+    }
+
+    fn send(&mut self, message: u8, byte: u8) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        if let Err(error) = stream.write_all(&[message, byte]) {
+            self.disconnect(Some(error));
+        }
+    }
+
+    /// Receives the next message, waiting for it until given deadline if any
+    fn receive(&mut self, deadline: Option<Instant>) -> Option<(u8, u8)> {
+        while self.buffer.len() < 2 {
+            let stream = self.stream.as_mut()?;
+            let result = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout.is_zero() {
+                        return None;
+                    }
+                    stream
+                        .set_nonblocking(false)
+                        .and_then(|_| stream.set_read_timeout(Some(timeout)))
+                }
+                None => stream.set_nonblocking(true),
+            };
+            if let Err(error) = result {
+                self.disconnect(Some(error));
+                return None;
+            }
+            let mut data = [0; 64];
+            match stream.read(&mut data) {
+                Ok(0) => {
+                    self.disconnect(None);
+                    return None;
+                }
+                Ok(length) => self.buffer.extend_from_slice(&data[..length]),
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    // Keep waiting only if there's a deadline that hasn't passed yet
+                    deadline?;
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.disconnect(Some(error));
+                    return None;
+                }
+            }
+        }
+        let message = (self.buffer[0], self.buffer[1]);
+        self.buffer.drain(..2);
+        Some(message)
+    }
+
+    /// Handles messages that arrived without waiting for them
+    fn poll(&mut self) {
+        while let Some((message, byte)) = self.receive(None) {
+            self.handle(message, byte);
+        }
+    }
+
+    /// Handles a message that isn't the reply to an ongoing transfer
+    fn handle(&mut self, message: u8, byte: u8) {
+        match message {
+            TRANSFER if self.pending_transfer.is_none() => self.pending_transfer = Some(byte),
+            // Only one transfer can be waiting at a time, which shouldn't happen in lockstep
+            TRANSFER => self.send(REPLY, 0xFF),
+            REPLY if self.stale_replies > 0 => self.stale_replies -= 1,
+            _ => log::warn!("Unexpected link cable message {message:#04X}"),
+        }
+    }
+
+    /// Replies to a transfer started by the other instance that the Game Boy isn't ready for
+    fn refuse_pending(&mut self) {
+        if self.pending_transfer.take().is_some() {
+            self.send(REPLY, 0xFF);
+        }
+    }
+}
+
+impl SerialDevice for TcpLink {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        // If both instances are using the internal clock, neither receives anything
+        self.poll();
+        self.refuse_pending();
+
+        self.send(TRANSFER, byte);
+        let deadline = Instant::now() + Self::TIMEOUT;
+        while let Some((message, data)) = self.receive(Some(deadline)) {
+            match message {
+                REPLY if self.stale_replies == 0 => return data,
+                TRANSFER => {
+                    self.pending_transfer = Some(data);
+                    self.refuse_pending();
+                }
+                _ => self.handle(message, data),
+            }
+        }
+        if self.connected() {
+            log::warn!("Link cable transfer timed out");
+            self.stale_replies += 1;
+        }
+        0xFF
+    }
+
+    fn external_transfer(&mut self, byte: u8) -> Option<u8> {
+        self.poll();
+        let received = self.pending_transfer.take()?;
+        self.send(REPLY, byte);
+        Some(received)
+    }
+
+    fn idle(&mut self) {
+        self.poll();
+        self.refuse_pending();
+    }
+}
@@ -0,0 +1,287 @@
+use super::*;
+
+/// Width of the image captured by the Game Boy Camera sensor
+pub const CAMERA_WIDTH: usize = 128;
+/// Height of the image captured by the Game Boy Camera sensor
+pub const CAMERA_HEIGHT: usize = 112;
+
+/// Grayscale image captured by the Game Boy Camera sensor.
+/// Each byte is the brightness of a pixel from 0 (black) to 255 (white), stored row by row
+pub type CameraImage = [u8; CAMERA_WIDTH * CAMERA_HEIGHT];
+
+/// Source of the images captured by the Game Boy Camera.
+/// Can be implemented by the host to provide images from a webcam or a file
+pub trait CameraSource {
+    /// Writes the current view of the camera into given image
+    fn capture(&mut self, image: &mut CameraImage);
+}
+
+/// Camera source that shows a fixed test pattern of gradients and shapes
+pub struct TestPatternCamera;
+
+impl CameraSource for TestPatternCamera {
+    fn capture(&mut self, image: &mut CameraImage) {
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                // Horizontal gradient in the top half, vertical bars in the bottom half
+                let mut value = if y < CAMERA_HEIGHT / 2 {
+                    (x * 255 / (CAMERA_WIDTH - 1)) as u8
+                } else {
+                    [0, 85, 170, 255][x * 4 / CAMERA_WIDTH]
+                };
+                // Draw an inverted circle in the middle
+                let dx = x as i32 - (CAMERA_WIDTH / 2) as i32;
+                let dy = y as i32 - (CAMERA_HEIGHT / 2) as i32;
+                if dx * dx + dy * dy < 32 * 32 {
+                    value = 255 - value;
+                }
+                image[y * CAMERA_WIDTH + x] = value;
+            }
+        }
+    }
+}
+
+/// Camera source that always captures the same image
+pub struct StaticImageCamera {
+    pub image: CameraImage,
+}
+
+impl StaticImageCamera {
+    pub fn new(image: CameraImage) -> Self {
+        Self { image }
+    }
+}
+
+impl CameraSource for StaticImageCamera {
+    fn capture(&mut self, image: &mut CameraImage) {
+        image.copy_from_slice(&self.image);
+    }
+}
+
+pub(super) fn test_pattern_camera() -> Box<dyn CameraSource> {
+    Box::new(TestPatternCamera)
+}
+
+/// Simulates the sensor registers and image processing of the Game Boy Camera
+#[derive(Deserialize, Serialize)]
+pub struct Camera {
+    /// Registers mapped to $A000-$A035 when RAM bank $10 is selected
+    #[serde(with = "BigArray")]
+    registers: [u8; 0x36],
+    /// T-cycles left until the ongoing capture finishes
+    capture_cycles: u32,
+}
+
+impl Camera {
+    /// Offset of the captured image in the first RAM bank
+    const IMAGE_ADDRESS: usize = 0x0100;
+    /// Start of the 4x4 dithering matrix registers, where each matrix cell has three thresholds
+    const MATRIX_ADDRESS: usize = 0x06;
+    /// Multiplier applied to the sensor values by each edge enhancement ratio setting
+    const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x36],
+            capture_cycles: 0,
+        }
+    }
+
+    /// If a capture is currently in progress
+    pub fn busy(&self) -> bool {
+        self.registers[0] & 1 > 0
+    }
+
+    /// Returns value from camera register at given offset
+    pub fn read(&self, offset: usize) -> u8 {
+        // Only the control register can be read back
+        if offset == 0 { self.registers[0] } else { 0x00 }
+    }
+
+    /// Writes value to camera register at given offset
+    pub fn write(&mut self, offset: usize, value: u8) {
+        match offset {
+            0x00 => {
+                let start = value & 1 > 0;
+                if start && !self.busy() {
+                    // The capture time depends on the exposure time and the N flag
+                    let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]);
+                    let n_flag = self.registers[1] & 0x80 > 0;
+                    self.capture_cycles =
+                        129792 + if n_flag { 0 } else { 2048 } + (exposure as u32) * 64;
+                }
+                // Writing 0 to the start bit cancels the capture
+                if !start {
+                    self.capture_cycles = 0;
+                }
+                self.registers[0] = value & 0b111;
+            }
+            0x01..=0x35 => self.registers[offset] = value,
+            _ => {}
+        }
+    }
+
+    /// Cycles the camera forward by one T-cycle,
+    /// returns true when a capture is finished and the image should be processed
+    pub fn cycle(&mut self) -> bool {
+        if self.capture_cycles == 0 {
+            return false;
+        }
+        self.capture_cycles -= 1;
+        if self.capture_cycles == 0 {
+            self.registers[0] &= !1;
+            return true;
+        }
+        false
+    }
+
+    /// Applies sensor gain, exposure and edge enhancement to the raw captured image
+    fn process(&self, raw: &CameraImage) -> Vec<f32> {
+        let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]) as f32;
+        // Gain is set in roughly 0.5 dB steps from 14 dB
+        let gain_db = 14.0 + (self.registers[1] & 0x1F) as f32 * 0.5;
+        let gain = 10f32.powf((gain_db - 26.0) / 20.0);
+        let exposed: Vec<f32> = raw
+            .iter()
+            .map(|&value| value as f32 * gain * exposure / 0x1000 as f32)
+            .collect();
+
+        // Edge enhancement is only applied when all three VH and N bits are set
+        if self.registers[1] & 0xE0 != 0xE0 {
+            return exposed;
+        }
+        let ratio = Self::EDGE_RATIOS[((self.registers[4] >> 4) & 0b111) as usize];
+        let pixel = |x: i32, y: i32| {
+            let x = x.clamp(0, CAMERA_WIDTH as i32 - 1) as usize;
+            let y = y.clamp(0, CAMERA_HEIGHT as i32 - 1) as usize;
+            exposed[y * CAMERA_WIDTH + x]
+        };
+        let mut enhanced = exposed.clone();
+        for y in 0..CAMERA_HEIGHT as i32 {
+            for x in 0..CAMERA_WIDTH as i32 {
+                let neighbors =
+                    pixel(x - 1, y) + pixel(x + 1, y) + pixel(x, y - 1) + pixel(x, y + 1);
+                enhanced[y as usize * CAMERA_WIDTH + x as usize] =
+                    pixel(x, y) + (4.0 * pixel(x, y) - neighbors) * ratio;
+            }
+        }
+        enhanced
+    }
+
+    /// Converts the raw captured image into Game Boy tiles using the dithering matrix,
+    /// and writes them to the start of the first RAM bank
+    pub fn develop(&self, raw: &CameraImage, ram: &mut [u8]) {
+        let processed = self.process(raw);
+        let invert = self.registers[4] & 0b1000 > 0;
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let mut value = processed[y * CAMERA_WIDTH + x].clamp(0.0, 255.0) as u8;
+                if invert {
+                    value = 255 - value;
+                }
+                // Compare value to the three thresholds of the matrix cell
+                let cell = Self::MATRIX_ADDRESS + ((y % 4) * 4 + (x % 4)) * 3;
+                let thresholds = &self.registers[cell..cell + 3];
+                let color: u8 = if value < thresholds[0] {
+                    3
+                } else if value < thresholds[1] {
+                    2
+                } else if value < thresholds[2] {
+                    1
+                } else {
+                    0
+                };
+
+                // Image is stored as 16x14 tiles, each row of a tile taking two bytes
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + (x / 8);
+                let address = Self::IMAGE_ADDRESS + tile * 16 + (y % 8) * 2;
+                if address + 1 >= ram.len() {
+                    return;
+                }
+                let mask = 0b1000_0000 >> (x % 8);
+                ram[address] = (ram[address] & !mask) | if color & 1 > 0 { mask } else { 0 };
+                ram[address + 1] =
+                    (ram[address + 1] & !mask) | if color & 2 > 0 { mask } else { 0 };
+            }
+        }
+    }
+}
+
+impl MBC {
+    pub(super) fn read_camera(&self, address: u16) -> u8 {
+        let Some(camera) = &self.camera else {
+            return 0xFF;
+        };
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address),
+            0x4000..=0x7FFF => {
+                address -= 0x4000;
+                address += self.rom_bank * 0x4000;
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                // Bank $10 maps the camera registers, which repeat every 128 bytes
+                if self.ram_bank & 0x10 > 0 {
+                    return camera.read(address & 0x7F);
+                }
+                // RAM can't be accessed while the camera is capturing an image
+                if camera.busy() {
+                    return 0x00;
+                }
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.read_ram(address)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_camera(&mut self, address: u16, value: u8) {
+        match address {
+            // Enable writing to RAM
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => {
+                self.rom_bank = self.mask_bank_number(value & 0x3F, self.info.rom_banks)
+            }
+            // RAM bank number or camera register select
+            0x4000..=0x5FFF => {
+                self.ram_bank = if value & 0x10 > 0 {
+                    0x10
+                } else {
+                    self.mask_bank_number(value, self.info.ram_banks)
+                };
+            }
+            0xA000..=0xBFFF => {
+                let Some(camera) = &mut self.camera else {
+                    return;
+                };
+                if self.ram_bank & 0x10 > 0 {
+                    camera.write((address & 0x7F) as usize, value);
+                    return;
+                }
+                if !self.ram_enabled || camera.busy() {
+                    return;
+                }
+                let mut address = address as usize;
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.write_ram(address, value);
+            }
+            _ => {}
+        }
+    }
+
+    /// Cycles the camera forward by one T-cycle and develops the image when capture finishes
+    pub(super) fn cycle_camera(&mut self) {
+        let Some(camera) = &mut self.camera else {
+            return;
+        };
+        if camera.cycle() {
+            let mut image = [0; CAMERA_WIDTH * CAMERA_HEIGHT];
+            self.camera_source.capture(&mut image);
+            camera.develop(&image, &mut self.ram);
+        }
+    }
+}
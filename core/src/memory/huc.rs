@@ -0,0 +1,348 @@
+use super::*;
+
+/// Infrared transceiver of HuC1 and HuC3 cartridges.
+/// Can be implemented by the host to connect the cartridge to another device
+pub trait InfraredPort {
+    /// Returns if infrared light is currently being received
+    fn receiving_light(&self) -> bool;
+
+    /// Called when the cartridge turns its infrared LED on or off
+    fn set_led(&mut self, _on: bool) {}
+}
+
+/// Infrared port that is never receiving any light
+pub struct NoInfrared;
+
+impl InfraredPort for NoInfrared {
+    fn receiving_light(&self) -> bool {
+        false
+    }
+}
+
+pub(super) fn no_infrared() -> Box<dyn InfraredPort> {
+    Box::new(NoInfrared)
+}
+
+/// Simulates the clock, tone generator and register state of HuC3 cartridges
+#[derive(Deserialize, Serialize)]
+pub struct HuC3 {
+    /// Selects what is mapped to $A000-$BFFF
+    mode: u8,
+    /// Address used by the clock memory commands
+    address: u8,
+    /// Response of the last read command
+    response: u8,
+    /// Last written command
+    command: u8,
+    /// Scratch memory that clock values are copied from and to, one nibble per address
+    #[serde(with = "BigArray")]
+    memory: [u8; 0x100],
+    /// Minutes elapsed this day (0-1439)
+    pub minutes: u16,
+    /// Days elapsed
+    pub days: u16,
+    /// Seconds elapsed this minute
+    seconds: u8,
+    /// T-cycles elapsed since the last second
+    subsecond_cycles: u32,
+    /// Host Unix timestamp in seconds that corresponds to the current clock state.
+    /// Zero if the clock hasn't been synchronized yet
+    timestamp: u64,
+    /// Alarm time in minutes
+    alarm_minutes: u16,
+    /// Alarm time in days
+    alarm_days: u16,
+    alarm_enabled: bool,
+    /// The tone currently played by the speaker, if any
+    pub tone: Option<u8>,
+}
+
+impl HuC3 {
+    const CYCLES_PER_SECOND: u32 = 4194304;
+    const MINUTES_PER_DAY: u16 = 1440;
+    /// Size of the clock data appended to save RAM
+    pub const SAVE_SIZE: usize = 17;
+    /// Nibble addresses of clock values in scratch memory
+    const MINUTES_ADDRESS: usize = 0x00;
+    const DAYS_ADDRESS: usize = 0x03;
+    const ALARM_MINUTES_ADDRESS: usize = 0x58;
+    const ALARM_DAYS_ADDRESS: usize = 0x5B;
+    const ALARM_ENABLE_ADDRESS: usize = 0x5F;
+    /// Nibble addresses of tone generator controls in scratch memory
+    const TONE_ENABLE_ADDRESS: usize = 0x26;
+    const TONE_SELECT_ADDRESS: usize = 0x27;
+
+    pub fn new() -> Self {
+        Self {
+            mode: 0,
+            address: 0,
+            response: 0,
+            command: 0,
+            memory: [0; 0x100],
+            minutes: 0,
+            days: 0,
+            seconds: 0,
+            subsecond_cycles: 0,
+            timestamp: 0,
+            alarm_minutes: 0,
+            alarm_days: 0,
+            alarm_enabled: false,
+            tone: None,
+        }
+    }
+
+    /// Cycles the clock forward by one T-cycle
+    pub fn cycle(&mut self) {
+        self.subsecond_cycles += 1;
+        if self.subsecond_cycles >= Self::CYCLES_PER_SECOND {
+            self.subsecond_cycles = 0;
+            self.advance(1);
+            if self.timestamp != 0 {
+                self.timestamp += 1;
+            }
+        }
+    }
+
+    /// Advances the clock to match given host Unix timestamp
+    pub fn sync(&mut self, timestamp: u64) {
+        // The first synchronization only sets the reference point
+        if self.timestamp != 0 && timestamp > self.timestamp {
+            self.advance(timestamp - self.timestamp);
+        }
+        self.timestamp = self.timestamp.max(timestamp);
+    }
+
+    /// Increments the clock by given amount of seconds
+    fn advance(&mut self, seconds: u64) {
+        let total_seconds = (self.seconds as u64) + seconds;
+        self.seconds = (total_seconds % 60) as u8;
+        let total_minutes = (self.minutes as u64) + total_seconds / 60;
+        let minutes_per_day = Self::MINUTES_PER_DAY as u64;
+        self.minutes = (total_minutes % minutes_per_day) as u16;
+        self.days = self
+            .days
+            .wrapping_add((total_minutes / minutes_per_day) as u16);
+    }
+
+    /// Reads a value spanning given amount of nibbles from scratch memory
+    fn read_nibbles(&self, address: usize, count: usize) -> u16 {
+        (0..count).fold(0, |value, i| {
+            value | ((self.memory[address + i] as u16 & 0xF) << (4 * i))
+        })
+    }
+
+    /// Writes a value spanning given amount of nibbles into scratch memory
+    fn write_nibbles(&mut self, address: usize, count: usize, value: u16) {
+        for i in 0..count {
+            self.memory[address + i] = ((value >> (4 * i)) & 0xF) as u8;
+        }
+    }
+
+    /// Executes a command written to the cartridge in mode $B
+    fn run_command(&mut self, value: u8) {
+        self.command = value >> 4;
+        let argument = value & 0x0F;
+        match self.command {
+            // Read nibble and increment address
+            0x1 => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            // Write nibble
+            0x2 => self.memory[self.address as usize] = argument,
+            // Write nibble and increment address
+            0x3 => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            // Set lower nibble of address
+            0x4 => self.address = (self.address & 0xF0) | argument,
+            // Set upper nibble of address
+            0x5 => self.address = (self.address & 0x0F) | (argument << 4),
+            // Extended commands
+            0x6 => match argument {
+                // Copy current time to scratch memory
+                0x0 => {
+                    self.write_nibbles(Self::MINUTES_ADDRESS, 3, self.minutes);
+                    self.write_nibbles(Self::DAYS_ADDRESS, 4, self.days);
+                }
+                // Copy time from scratch memory
+                0x1 => {
+                    self.minutes =
+                        self.read_nibbles(Self::MINUTES_ADDRESS, 3) % Self::MINUTES_PER_DAY;
+                    self.days = self.read_nibbles(Self::DAYS_ADDRESS, 4);
+                    self.seconds = 0;
+                    self.subsecond_cycles = 0;
+                    self.alarm_minutes = self.read_nibbles(Self::ALARM_MINUTES_ADDRESS, 3);
+                    self.alarm_days = self.read_nibbles(Self::ALARM_DAYS_ADDRESS, 4);
+                    self.alarm_enabled = self.memory[Self::ALARM_ENABLE_ADDRESS] & 1 > 0;
+                }
+                // Status check, the clock is always ready
+                0x2 => self.response = 0x1,
+                // Start or stop the tone generator
+                0xE => {
+                    self.tone = (self.memory[Self::TONE_ENABLE_ADDRESS] & 1 > 0)
+                        .then_some(self.memory[Self::TONE_SELECT_ADDRESS] & 0x0F);
+                }
+                _ => log::warn!("Unknown HuC3 extended command {:#03X}", argument),
+            },
+            _ => log::warn!("Unknown HuC3 command {:#04X}", value),
+        }
+    }
+
+    /// Serializes the clock state into the format that is appended to save RAM
+    pub fn to_save(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SAVE_SIZE);
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&self.minutes.to_le_bytes());
+        data.extend_from_slice(&self.days.to_le_bytes());
+        data.extend_from_slice(&self.alarm_minutes.to_le_bytes());
+        data.extend_from_slice(&self.alarm_days.to_le_bytes());
+        data.push(self.alarm_enabled as u8);
+        data
+    }
+
+    /// Restores the clock state from data appended to save RAM
+    pub fn load_save(&mut self, data: &[u8]) {
+        if data.len() < Self::SAVE_SIZE {
+            log::error!("Clock save data is only {} bytes long", data.len());
+            return;
+        }
+        let word = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        self.timestamp = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        self.minutes = word(8) % Self::MINUTES_PER_DAY;
+        self.days = word(10);
+        self.alarm_minutes = word(12);
+        self.alarm_days = word(14);
+        self.alarm_enabled = data[16] & 1 > 0;
+        self.seconds = 0;
+        self.subsecond_cycles = 0;
+    }
+}
+
+impl MBC {
+    pub(super) fn read_huc1(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address),
+            0x4000..=0x7FFF => {
+                address -= 0x4000;
+                address += self.rom_bank * 0x4000;
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                // Infrared mode replaces RAM with the receiver state
+                if self.ir_mode {
+                    return 0xC0 | self.infrared.receiving_light() as u8;
+                }
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.read_ram(address)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_huc1(&mut self, address: u16, value: u8) {
+        match address {
+            // Switch between RAM and infrared mode
+            0x0000..=0x1FFF => self.ir_mode = value == 0x0E,
+            // ROM bank number
+            0x2000..=0x3FFF => {
+                let mut masked = self.mask_bank_number(value & 0x3F, self.info.rom_banks);
+                if value & 0x3F == 0 {
+                    masked += 1;
+                }
+                self.rom_bank = masked;
+            }
+            // RAM bank number
+            0x4000..=0x5FFF if self.info.ram_banks != 0 => {
+                self.ram_bank = self.mask_bank_number(value, self.info.ram_banks);
+            }
+            // Write to RAM or set infrared LED
+            0xA000..=0xBFFF => {
+                if self.ir_mode {
+                    self.infrared.set_led(value & 1 > 0);
+                    return;
+                }
+                let mut address = address as usize;
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.write_ram(address, value);
+            }
+            _ => {}
+        };
+    }
+
+    pub(super) fn read_huc3(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address),
+            0x4000..=0x7FFF => {
+                address -= 0x4000;
+                address += self.rom_bank * 0x4000;
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                let Some(huc3) = &self.huc3 else {
+                    return 0xFF;
+                };
+                match huc3.mode {
+                    // RAM
+                    0x0 | 0xA => {
+                        address -= 0xA000;
+                        address += self.ram_bank * 0x2000;
+                        self.read_ram(address)
+                    }
+                    // Command response, with the command echoed in the upper nibble
+                    0xC => (huc3.command << 4) | (huc3.response & 0x0F),
+                    // Semaphore, the clock is always ready for new commands
+                    0xD => 0xFF,
+                    // Infrared receiver
+                    0xE => 0xC0 | self.infrared.receiving_light() as u8,
+                    _ => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_huc3(&mut self, address: u16, value: u8) {
+        match address {
+            // Select what is mapped to $A000-$BFFF
+            0x0000..=0x1FFF => {
+                if let Some(huc3) = &mut self.huc3 {
+                    huc3.mode = value & 0x0F;
+                }
+            }
+            // ROM bank number
+            0x2000..=0x3FFF => {
+                self.rom_bank = self.mask_bank_number(value & 0x7F, self.info.rom_banks)
+            }
+            // RAM bank number
+            0x4000..=0x5FFF if self.info.ram_banks != 0 => {
+                self.ram_bank = self.mask_bank_number(value, self.info.ram_banks);
+            }
+            0xA000..=0xBFFF => {
+                let Some(huc3) = &mut self.huc3 else {
+                    return;
+                };
+                match huc3.mode {
+                    // Write to RAM
+                    0xA => {
+                        let mut address = address as usize;
+                        address -= 0xA000;
+                        address += self.ram_bank * 0x2000;
+                        self.write_ram(address, value);
+                    }
+                    // Clock command
+                    0xB => huc3.run_command(value),
+                    // Infrared LED
+                    0xE => self.infrared.set_led(value & 1 > 0),
+                    _ => {}
+                }
+            }
+            _ => {}
+        };
+    }
+}
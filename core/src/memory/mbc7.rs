@@ -0,0 +1,338 @@
+use super::*;
+
+/// Current operation of the EEPROM
+#[derive(Deserialize, Serialize, Clone, Copy)]
+enum EEPROMState {
+    /// Waiting for the start bit of a command
+    Idle,
+    /// Receiving the opcode and address bits of a command
+    Command { bits: u16, count: u8 },
+    /// Shifting out data from given word address
+    Reading { address: u8, data: u16, count: u8 },
+    /// Receiving data to write into given word address, or all words if none
+    Writing {
+        address: Option<u8>,
+        data: u16,
+        count: u8,
+    },
+}
+
+/// Simulates the 93LC56 serial EEPROM used by MBC7 cartridges for saving.
+/// The EEPROM contents are stored in the cartridge RAM buffer
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub struct EEPROM {
+    /// Chip select pin
+    cs: bool,
+    /// Clock pin
+    clk: bool,
+    /// Data input pin
+    di: bool,
+    /// Data output pin
+    data_out: bool,
+    /// If write and erase commands are allowed
+    write_enabled: bool,
+    state: EEPROMState,
+}
+
+impl EEPROM {
+    /// Size of the EEPROM in bytes
+    pub const SIZE: usize = 256;
+
+    pub fn new() -> Self {
+        Self {
+            cs: false,
+            clk: false,
+            di: false,
+            data_out: true,
+            write_enabled: false,
+            state: EEPROMState::Idle,
+        }
+    }
+
+    /// Returns the pin states in the format of the MBC7 EEPROM register
+    pub fn read(&self) -> u8 {
+        ((self.cs as u8) << 7)
+            | ((self.clk as u8) << 6)
+            | ((self.di as u8) << 1)
+            | self.data_out as u8
+    }
+
+    /// Updates the pin states from a write to the MBC7 EEPROM register
+    pub fn write(&mut self, value: u8, memory: &mut [u8]) {
+        let cs = value & 0b1000_0000 > 0;
+        let clk = value & 0b0100_0000 > 0;
+        self.di = value & 0b0000_0010 > 0;
+
+        if !cs {
+            // Deselecting the chip cancels any unfinished command
+            self.state = EEPROMState::Idle;
+            self.data_out = true;
+        } else if clk && !self.clk && self.cs {
+            // Data is shifted on the rising edge of the clock
+            self.clock(memory);
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn read_word(memory: &[u8], address: u8) -> u16 {
+        let i = (address as usize & 0x7F) * 2;
+        u16::from_le_bytes([memory[i], memory[i + 1]])
+    }
+
+    fn write_word(&self, memory: &mut [u8], address: u8, value: u16) {
+        if !self.write_enabled {
+            return;
+        }
+        let i = (address as usize & 0x7F) * 2;
+        memory[i..i + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn clock(&mut self, memory: &mut [u8]) {
+        use EEPROMState::*;
+        let di = self.di as u16;
+        self.state = match self.state {
+            Idle => {
+                // Leading zeros before the start bit are ignored
+                if di == 1 {
+                    Command { bits: 0, count: 0 }
+                } else {
+                    Idle
+                }
+            }
+            Command { bits, count } => {
+                let bits = (bits << 1) | di;
+                // Commands consist of two opcode bits and eight address bits
+                if count + 1 < 10 {
+                    Command {
+                        bits,
+                        count: count + 1,
+                    }
+                } else {
+                    self.run_command(bits, memory)
+                }
+            }
+            Reading {
+                address,
+                data,
+                count,
+            } => {
+                self.data_out = data & 0x8000 > 0;
+                if count > 1 {
+                    Reading {
+                        address,
+                        data: data << 1,
+                        count: count - 1,
+                    }
+                } else {
+                    // Reading continues sequentially from the next word
+                    let address = address.wrapping_add(1) & 0x7F;
+                    Reading {
+                        address,
+                        data: Self::read_word(memory, address),
+                        count: 16,
+                    }
+                }
+            }
+            Writing {
+                address,
+                data,
+                count,
+            } => {
+                let data = (data << 1) | di;
+                if count + 1 < 16 {
+                    Writing {
+                        address,
+                        data,
+                        count: count + 1,
+                    }
+                } else {
+                    match address {
+                        Some(address) => self.write_word(memory, address, data),
+                        None => {
+                            for address in 0..0x80 {
+                                self.write_word(memory, address, data);
+                            }
+                        }
+                    }
+                    // Writes are finished instantly, so the chip is always ready
+                    self.data_out = true;
+                    Idle
+                }
+            }
+        };
+    }
+
+    fn run_command(&mut self, bits: u16, memory: &mut [u8]) -> EEPROMState {
+        use EEPROMState::*;
+        let address = bits as u8;
+        match bits >> 8 {
+            // READ
+            0b10 => {
+                // A dummy zero bit is output before the data
+                self.data_out = false;
+                let address = address & 0x7F;
+                Reading {
+                    address,
+                    data: Self::read_word(memory, address),
+                    count: 16,
+                }
+            }
+            // WRITE
+            0b01 => Writing {
+                address: Some(address & 0x7F),
+                data: 0,
+                count: 0,
+            },
+            // ERASE
+            0b11 => {
+                self.write_word(memory, address, 0xFFFF);
+                self.data_out = true;
+                Idle
+            }
+            // Commands without an address use the upper address bits as the opcode
+            _ => match address >> 6 {
+                // EWDS
+                0b00 => {
+                    self.write_enabled = false;
+                    Idle
+                }
+                // WRAL
+                0b01 => Writing {
+                    address: None,
+                    data: 0,
+                    count: 0,
+                },
+                // ERAL
+                0b10 => {
+                    for address in 0..0x80 {
+                        self.write_word(memory, address, 0xFFFF);
+                    }
+                    self.data_out = true;
+                    Idle
+                }
+                // EWEN
+                _ => {
+                    self.write_enabled = true;
+                    Idle
+                }
+            },
+        }
+    }
+}
+
+/// Simulates the accelerometer and EEPROM hardware of MBC7 cartridges
+#[derive(Deserialize, Serialize)]
+pub struct MBC7 {
+    /// The second RAM enable register, which also has to be set to access the registers
+    ram_enabled: bool,
+    /// Current tilt of the cartridge on the X and Y axes, in g
+    tilt: (f32, f32),
+    /// Latched accelerometer values readable by the CPU
+    latched: (u16, u16),
+    /// If the latched values have been erased, which is required before latching
+    erased: bool,
+    pub eeprom: EEPROM,
+}
+
+impl MBC7 {
+    /// The accelerometer value when cartridge is level
+    const CENTER: f32 = 0x81D0 as f32;
+    /// The change in accelerometer value per 1 g of acceleration
+    const GRAVITY: f32 = 0x70 as f32;
+
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            tilt: (0.0, 0.0),
+            latched: (0x8000, 0x8000),
+            erased: false,
+            eeprom: EEPROM::new(),
+        }
+    }
+
+    /// Sets the tilt of the cartridge
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn sensor_value(tilt: f32) -> u16 {
+        (Self::CENTER + tilt.clamp(-4.0, 4.0) * Self::GRAVITY) as u16
+    }
+}
+
+impl MBC {
+    pub(super) fn read_mbc7(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address),
+            0x4000..=0x7FFF => {
+                address -= 0x4000;
+                address += self.rom_bank * 0x4000;
+                self.read_rom(address)
+            }
+            // Registers are only accessible when both RAM enable registers are set
+            0xA000..=0xAFFF => {
+                let Some(mbc7) = &self.mbc7 else {
+                    return 0xFF;
+                };
+                if !self.ram_enabled || !mbc7.ram_enabled {
+                    return 0xFF;
+                }
+                // Address bits 4-7 select the register
+                match (address >> 4) & 0xF {
+                    0x2 => mbc7.latched.0 as u8,
+                    0x3 => (mbc7.latched.0 >> 8) as u8,
+                    0x4 => mbc7.latched.1 as u8,
+                    0x5 => (mbc7.latched.1 >> 8) as u8,
+                    0x6 => 0x00,
+                    0x8 => mbc7.eeprom.read(),
+                    _ => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_mbc7(&mut self, address: u16, value: u8) {
+        match address {
+            // Enable the RAM
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => self.rom_bank = self.mask_bank_number(value, self.info.rom_banks),
+            // Second RAM enable register
+            0x4000..=0x5FFF => {
+                if let Some(mbc7) = &mut self.mbc7 {
+                    mbc7.ram_enabled = value == 0x40;
+                }
+            }
+            0xA000..=0xAFFF => {
+                let Some(mbc7) = &mut self.mbc7 else {
+                    return;
+                };
+                if !self.ram_enabled || !mbc7.ram_enabled {
+                    return;
+                }
+                match (address >> 4) & 0xF {
+                    // Erase latched accelerometer data
+                    0x0 if value == 0x55 => {
+                        mbc7.latched = (0x8000, 0x8000);
+                        mbc7.erased = true;
+                    }
+                    // Latch accelerometer data
+                    0x1 if value == 0xAA && mbc7.erased => {
+                        mbc7.latched = (
+                            MBC7::sensor_value(mbc7.tilt.0),
+                            MBC7::sensor_value(mbc7.tilt.1),
+                        );
+                        mbc7.erased = false;
+                    }
+                    0x8 => mbc7.eeprom.write(value, &mut self.ram),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
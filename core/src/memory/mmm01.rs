@@ -0,0 +1,181 @@
+use super::*;
+
+/// Simulates the registers of MMM01 multicart cartridges
+#[derive(Deserialize, Serialize)]
+pub struct MMM01 {
+    /// If the game has been selected by the menu,
+    /// which locks the multicart configuration registers
+    locked: bool,
+    /// Bits 0-4 of the ROM bank number
+    rom_bank_low: u8,
+    /// Bits 5-6 of the ROM bank number
+    rom_bank_mid: u8,
+    /// Bits 7-8 of the ROM bank number
+    rom_bank_high: u8,
+    /// Bits 1-4 of the lower ROM bank register that can't be changed by the game
+    rom_bank_mask: u8,
+    /// Bits 0-1 of the RAM bank number
+    ram_bank_low: u8,
+    /// Bits 2-3 of the RAM bank number
+    ram_bank_high: u8,
+    /// Bits of the lower RAM bank register that can't be changed by the game
+    ram_bank_mask: u8,
+    /// If the 2-bit bank register is also applied to $0000-$3FFF (MBC1 advanced banking mode)
+    mbc1_mode: bool,
+    /// If the game is prevented from changing the banking mode
+    mbc1_mode_disabled: bool,
+    /// If the 2-bit bank register and the middle ROM bank bits are swapped
+    multiplex: bool,
+}
+
+impl MMM01 {
+    pub fn new() -> Self {
+        Self {
+            locked: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mbc1_mode: false,
+            mbc1_mode_disabled: false,
+            multiplex: false,
+        }
+    }
+
+    /// Returns the ROM banks mapped to $0000-$3FFF and $4000-$7FFF
+    fn rom_banks(&self, bank_amount: u16) -> (usize, usize) {
+        // Before the menu has selected a game,
+        // the last 32 KiB of the ROM containing the menu is mapped
+        if !self.locked {
+            let last = bank_amount.max(2) as usize - 1;
+            return (last - 1, last);
+        }
+        // In multiplex mode the middle ROM bank bits are taken from the 2-bit bank register,
+        // which like on MBC1 is only applied to $0000-$3FFF in advanced banking mode
+        let (middle, middle0) = if self.multiplex {
+            let middle0 = if self.mbc1_mode { self.ram_bank_low } else { 0 };
+            (self.ram_bank_low, middle0)
+        } else {
+            (self.rom_bank_mid, self.rom_bank_mid)
+        };
+        let high = (self.rom_bank_high as usize) << 7;
+        // The first bank of the selected game is mapped to $0000-$3FFF
+        let bank0 = (self.rom_bank_low & (self.rom_bank_mask << 1)) as usize
+            | ((middle0 as usize) << 5)
+            | high;
+        let mut bank = self.rom_bank_low as usize | ((middle as usize) << 5) | high;
+        // Like with MBC1, the first bank can't be mapped to $4000-$7FFF
+        if bank == bank0 {
+            bank += 1;
+        }
+        let mask = bank_amount.max(1) as usize - 1;
+        (bank0 & mask, bank & mask)
+    }
+
+    /// Returns the currently mapped RAM bank
+    fn ram_bank(&self) -> usize {
+        let low = if self.multiplex {
+            self.rom_bank_mid
+        } else {
+            self.ram_bank_low
+        };
+        (low as usize) | ((self.ram_bank_high as usize) << 2)
+    }
+}
+
+impl MBC {
+    pub(super) fn read_mmm01(&self, address: u16) -> u8 {
+        let Some(mmm01) = &self.mmm01 else {
+            return 0xFF;
+        };
+        let address = address as usize;
+        let (bank0, bank) = mmm01.rom_banks(self.info.rom_banks);
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address + bank0 * 0x4000),
+            0x4000..=0x7FFF => self.read_rom(address - 0x4000 + bank * 0x4000),
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled || self.info.ram_banks == 0 {
+                    return 0xFF;
+                }
+                let bank = mmm01.ram_bank() % (self.info.ram_banks as usize);
+                self.read_ram(address - 0xA000 + bank * 0x2000)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub(super) fn write_mmm01(&mut self, address: u16, value: u8) {
+        let Some(mmm01) = &mut self.mmm01 else {
+            return;
+        };
+        // Bits that configure the multicart can only be written while the menu is running
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+                if !mmm01.locked {
+                    mmm01.ram_bank_mask = (value >> 4) & 0b11;
+                    // Selects the game and locks the configuration
+                    mmm01.locked = value & 0b0100_0000 > 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                if !mmm01.locked {
+                    mmm01.rom_bank_mid = (value >> 5) & 0b11;
+                }
+                let mask = mmm01.rom_bank_mask << 1;
+                mmm01.rom_bank_low = ((mmm01.rom_bank_low & mask) | (value & !mask)) & 0x1F;
+            }
+            0x4000..=0x5FFF => {
+                let mask = mmm01.ram_bank_mask;
+                mmm01.ram_bank_low = ((mmm01.ram_bank_low & mask) | (value & !mask)) & 0b11;
+                if !mmm01.locked {
+                    mmm01.ram_bank_high = (value >> 2) & 0b11;
+                    mmm01.rom_bank_high = (value >> 4) & 0b11;
+                    mmm01.mbc1_mode_disabled = value & 0b0100_0000 > 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !mmm01.mbc1_mode_disabled {
+                    mmm01.mbc1_mode = value & 1 > 0;
+                }
+                if !mmm01.locked {
+                    mmm01.rom_bank_mask = (value >> 2) & 0x0F;
+                    mmm01.multiplex = value & 0b0100_0000 > 0;
+                }
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled || self.info.ram_banks == 0 {
+                    return;
+                }
+                let bank = mmm01.ram_bank() % (self.info.ram_banks as usize);
+                self.write_ram(address as usize - 0xA000 + bank * 0x2000, value);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the ROM banks mapped after selecting a game in multiplex mode
+    fn multiplexed_banks(mbc1_mode: bool) -> (usize, usize) {
+        let mut mmm01 = MMM01::new();
+        mmm01.locked = true;
+        mmm01.multiplex = true;
+        mmm01.mbc1_mode = mbc1_mode;
+        mmm01.rom_bank_low = 0x01;
+        mmm01.ram_bank_low = 0b10;
+        mmm01.rom_banks(512)
+    }
+
+    #[test]
+    fn multiplexed_bank_register_applies_to_first_range_in_mbc1_mode() {
+        assert_eq!(multiplexed_banks(false), (0x00, 0x41));
+        assert_eq!(multiplexed_banks(true), (0x40, 0x41));
+    }
+}
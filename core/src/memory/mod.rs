@@ -0,0 +1,885 @@
+use super::*;
+
+mod camera;
+mod huc;
+mod mbc7;
+mod mmm01;
+mod rtc;
+use camera::*;
+pub use camera::{
+    CAMERA_HEIGHT, CAMERA_WIDTH, CameraImage, CameraSource, StaticImageCamera, TestPatternCamera,
+};
+use huc::*;
+pub use huc::{InfraredPort, NoInfrared};
+use mbc7::*;
+use mmm01::*;
+use rtc::*;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub enum MBCType {
+    NoMBC,
+    MBC1,
+    MBC2,
+    MMM01,
+    MBC3,
+    MBC5,
+    MBC6,
+    MBC7,
+    PocketCamera,
+    HuC3,
+    HuC1,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CartridgeInfo {
+    /// Type of memory bank controller
+    pub mbc: MBCType,
+    /// If cartridge provides external RAM
+    pub has_ram: bool,
+    /// If cartridge has battery, meaning it can store external RAM in itself
+    /// (a.k.a. saving is possible)
+    pub has_battery: bool,
+    /// If cartridge has a real-time clock
+    pub has_timer: bool,
+    /// Amount of 16 KiB ROM banks cartridge provides
+    pub rom_banks: u16,
+    /// Amount of 8 KiB RAM banks cartridge provides
+    pub ram_banks: u16,
+    /// Title of the cartridge
+    pub title: String,
+    /// If cartridge supports Game Boy Color features, either exclusively or alongside DMG
+    pub cgb_support: bool,
+    /// If cartridge supports Super Game Boy features
+    pub sgb_support: bool,
+}
+
+impl CartridgeInfo {
+    /// Returns info about cartridge features from the ROM header
+    pub fn from_header(header: &[u8]) -> Self {
+        let mbc = match header[0x47] {
+            0x01..=0x03 => MBCType::MBC1,
+            0x05..=0x06 => MBCType::MBC2,
+            0x0B..=0x0D => MBCType::MMM01,
+            0x0F..=0x13 => MBCType::MBC3,
+            0x19..=0x1E => MBCType::MBC5,
+            0x20 => MBCType::MBC6,
+            0x22 => MBCType::MBC7,
+            0xFC => MBCType::PocketCamera,
+            0xFE => MBCType::HuC3,
+            0xFF => MBCType::HuC1,
+            _ => MBCType::NoMBC,
+        };
+        // MBC2 has its own built-in RAM
+        let has_ram = matches!(
+            header[0x47],
+            0x02 | 0x03
+                | 0x05
+                | 0x06
+                | 0x0C
+                | 0x0D
+                | 0x10
+                | 0x12
+                | 0x13
+                | 0x1A
+                | 0x1B
+                | 0x1D
+                | 0x1E
+                | 0x22
+                | 0xFC
+                | 0xFE
+                | 0xFF
+        );
+        let has_battery = matches!(
+            header[0x47],
+            0x03 | 0x06 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFC | 0xFE | 0xFF
+        );
+        let has_timer = matches!(header[0x47], 0x0F | 0x10 | 0xFE);
+        let rom_banks = 2u16.saturating_pow(1 + (header[0x48] as u32));
+        let ram_banks = if !has_ram {
+            0
+        } else {
+            match header[0x49] {
+                0x02 => 1,
+                0x03 => 4,
+                0x04 => 16,
+                0x05 => 8,
+                _ => 0,
+            }
+        };
+        // CGB flag is $80 for cartridges that also work on DMG and $C0 for CGB-only ones
+        let cgb_support = header[0x43] & 0x80 > 0;
+        // SGB functions also require the old licensee code to defer to the new one
+        let sgb_support = header[0x46] == 0x03 && header[0x4B] == 0x33;
+        let title = std::str::from_utf8(&header[0x34..=0x42])
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            mbc,
+            has_ram,
+            has_battery,
+            has_timer,
+            rom_banks,
+            ram_banks,
+            title,
+            cgb_support,
+            sgb_support,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MemoryInitializationErrorType {
+    NoHeader,
+    UnimplementedMBC(MBCType),
+    /// Boot ROM isn't 256 bytes long, contains the actual length
+    InvalidBootROM(usize),
+    /// GBS music file can't be played, contains the reason
+    InvalidGBS(String),
+}
+
+#[derive(Debug)]
+pub struct MemoryInitializationError {
+    pub(crate) error_type: MemoryInitializationErrorType,
+}
+
+impl std::fmt::Display for MemoryInitializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error_type {
+            MemoryInitializationErrorType::NoHeader => {
+                write!(f, "ROM doesn't contain header")
+            }
+            MemoryInitializationErrorType::UnimplementedMBC(mbc) => {
+                write!(f, "MBC type {:?} isn't yet implemented. Sorry!", mbc)
+            }
+            MemoryInitializationErrorType::InvalidBootROM(length) => {
+                write!(
+                    f,
+                    "Boot ROM should be 256 bytes long, but it's {length} bytes"
+                )
+            }
+            MemoryInitializationErrorType::InvalidGBS(reason) => {
+                write!(f, "Invalid GBS file: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoryInitializationError {}
+
+#[derive(Deserialize, Serialize)]
+pub struct Memory {
+    /// Work RAM, of which DMG only uses the first two 4 KiB banks
+    #[serde(with = "BigArray")]
+    pub wram: [u8; 0x8000],
+    /// Work RAM bank mapped to $D000-$DFFF on CGB (SVBK), where bank 0 selects bank 1
+    wram_bank: u8,
+    #[serde(with = "BigArray")]
+    pub hram: [u8; 0x7F],
+    pub info: CartridgeInfo,
+    mbc: MBC,
+    /// Boot ROM that is mapped over the start of the cartridge ROM until it's disabled
+    boot_rom: Option<Vec<u8>>,
+}
+
+impl Memory {
+    pub fn new(rom: Vec<u8>) -> Result<Self, MemoryInitializationError> {
+        if rom.len() < 0x014F {
+            return Err(MemoryInitializationError {
+                error_type: MemoryInitializationErrorType::NoHeader,
+            });
+        }
+        let mut info = CartridgeInfo::from_header(&rom[0x0100..=0x014F]);
+        // MMM01 multicarts start by running the menu in the last 32 KiB of the ROM,
+        // so the header describing the whole cartridge is found there
+        if rom.len() >= 0x10000 {
+            let menu = rom.len() - 0x8000;
+            let menu_info = CartridgeInfo::from_header(&rom[menu + 0x0100..=menu + 0x014F]);
+            if matches!(menu_info.mbc, MBCType::MMM01) {
+                info = menu_info;
+            }
+        }
+        if !matches!(
+            info.mbc,
+            MBCType::NoMBC
+                | MBCType::MBC1
+                | MBCType::MBC2
+                | MBCType::MMM01
+                | MBCType::MBC3
+                | MBCType::MBC5
+                | MBCType::MBC7
+                | MBCType::PocketCamera
+                | MBCType::HuC1
+                | MBCType::HuC3
+        ) {
+            return Err(MemoryInitializationError {
+                error_type: MemoryInitializationErrorType::UnimplementedMBC(info.mbc),
+            });
+        }
+        let mbc = MBC::init(rom, info.clone());
+
+        Ok(Self {
+            wram: [0; 0x8000],
+            wram_bank: 1,
+            hram: [0; 0x7F],
+            mbc,
+            info,
+            boot_rom: None,
+        })
+    }
+
+    /// Creates memory for playing a GBS music file from the ROM built for it.
+    /// GBS sound drivers expect MBC5-like ROM banking and 8 KiB of RAM that is always enabled
+    pub fn for_gbs(rom: Vec<u8>, title: String) -> Self {
+        let info = CartridgeInfo {
+            mbc: MBCType::MBC5,
+            has_ram: true,
+            has_battery: false,
+            has_timer: false,
+            rom_banks: (rom.len() / 0x4000) as u16,
+            ram_banks: 1,
+            title,
+            cgb_support: false,
+            sgb_support: false,
+        };
+        let mut mbc = MBC::init(rom, info.clone());
+        mbc.ram_enabled = true;
+        Self {
+            wram: [0; 0x8000],
+            wram_bank: 1,
+            hram: [0; 0x7F],
+            mbc,
+            info,
+            boot_rom: None,
+        }
+    }
+
+    /// Maps given boot ROM over $0000-$00FF until it's disabled by writing to $FF50
+    pub fn set_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), MemoryInitializationError> {
+        if boot_rom.len() != 0x100 {
+            return Err(MemoryInitializationError {
+                error_type: MemoryInitializationErrorType::InvalidBootROM(boot_rom.len()),
+            });
+        }
+        self.boot_rom = Some(boot_rom);
+        Ok(())
+    }
+
+    /// Overwrites ROM of simulated cartridge
+    pub fn set_rom(&mut self, rom: Vec<u8>) {
+        self.mbc.rom = rom;
+    }
+
+    /// Overwrites RAM of simulated cartridge.
+    /// If cartridge has a real-time clock, its state is read from the end of the buffer
+    pub fn set_ram(&mut self, mut ram: Vec<u8>) {
+        let ram_size = 0x2000 * usize::from(self.info.ram_banks);
+        if ram.len() > ram_size {
+            if let Some(rtc) = &mut self.mbc.rtc {
+                rtc.load_save(&ram[ram_size..]);
+                ram.truncate(ram_size);
+            } else if let Some(huc3) = &mut self.mbc.huc3 {
+                huc3.load_save(&ram[ram_size..]);
+                ram.truncate(ram_size);
+            }
+        }
+        // EEPROM commands can address every word, so the save has to cover the whole chip.
+        // Missing words read as erased
+        if matches!(self.info.mbc, MBCType::MBC7) {
+            ram.resize(EEPROM::SIZE, 0xFF);
+        }
+        self.mbc.ram = ram;
+    }
+
+    /// Zeroes work RAM, high RAM and cartridge RAM
+    pub fn clear_ram(&mut self) {
+        self.wram.fill(0);
+        self.hram.fill(0);
+        self.mbc.ram.fill(0);
+    }
+
+    /// Returns copy of RAM buffer in simulated cartridge.
+    /// If cartridge has a real-time clock, its state is appended to the end of the buffer
+    pub fn get_ram(&self) -> Vec<u8> {
+        let mut ram = self.mbc.ram.clone();
+        if let Some(rtc) = &self.mbc.rtc {
+            ram.extend(rtc.to_save());
+        } else if let Some(huc3) = &self.mbc.huc3 {
+            ram.extend(huc3.to_save());
+        }
+        ram
+    }
+
+    /// Advances the cartridge real-time clock to match given Unix timestamp in seconds
+    pub fn sync_rtc(&mut self, timestamp: u64) {
+        if let Some(rtc) = &mut self.mbc.rtc {
+            rtc.sync(timestamp);
+        }
+        if let Some(huc3) = &mut self.mbc.huc3 {
+            huc3.sync(timestamp);
+        }
+    }
+
+    /// Connects the infrared port of HuC1 and HuC3 cartridges to given device
+    pub fn set_infrared_port(&mut self, port: Box<dyn InfraredPort>) {
+        self.mbc.infrared = port;
+    }
+
+    /// Returns the tone played by the speaker of HuC3 cartridges, if any
+    pub fn get_tone(&self) -> Option<u8> {
+        self.mbc.huc3.as_ref().and_then(|huc3| huc3.tone)
+    }
+
+    /// Sets the image source of the Game Boy Camera
+    pub fn set_camera_source(&mut self, source: Box<dyn CameraSource>) {
+        self.mbc.camera_source = source;
+    }
+
+    /// Sets the tilt of cartridges with an accelerometer
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Some(mbc7) = &mut self.mbc.mbc7 {
+            mbc7.set_tilt(x, y);
+        }
+    }
+
+    /// Returns index in work RAM for given address in $C000-$DFFF
+    fn wram_index(&self, address: u16) -> usize {
+        match address {
+            0xC000..=0xCFFF => usize::from(address - 0xC000),
+            _ => 0x1000 * usize::from(self.wram_bank.max(1)) + usize::from(address - 0xD000),
+        }
+    }
+
+    /// Cycles cartridge hardware forward by one T-cycle
+    pub fn cycle(&mut self) {
+        if let Some(rtc) = &mut self.mbc.rtc {
+            rtc.cycle();
+        }
+        if let Some(huc3) = &mut self.mbc.huc3 {
+            huc3.cycle();
+        }
+        self.mbc.cycle_camera();
+    }
+}
+
+impl MemoryAccess for Memory {
+    fn mem_read(&self, address: u16) -> u8 {
+        if let Some(boot_rom) = &self.boot_rom
+            && address < 0x0100
+        {
+            return boot_rom[address as usize];
+        }
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => self.mbc.read(address),
+            0xC000..=0xDFFF => self.wram[self.wram_index(address)],
+            0xE000..=0xFDFF => self.wram[self.wram_index(address - 0x2000)],
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize],
+            // Boot ROM disable register can't be read
+            0xFF50 => 0xFF,
+            // SVBK
+            0xFF70 => 0b1111_1000 | self.wram_bank,
+            _ => 0,
+        }
+    }
+    fn mem_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => self.mbc.write(address, value),
+            0xC000..=0xDFFF => self.wram[self.wram_index(address)] = value,
+            0xE000..=0xFDFF => self.wram[self.wram_index(address - 0x2000)] = value,
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize] = value,
+            // Unmaps the boot ROM until the system is reset
+            0xFF50 if value & 1 > 0 => self.boot_rom = None,
+            // SVBK
+            0xFF70 => self.wram_bank = value & 0b111,
+            _ => {}
+        }
+    }
+}
+
+/// Simulates behavior of MBC cartridges
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+struct MBC {
+    // ROM is loaded manually using load_rom function
+    // This is so ROM isnt also saved in the save state for no reason
+    #[serde(skip_serializing, skip_deserializing)]
+    pub rom: Vec<u8>,
+    pub ram: Vec<u8>,
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    info: CartridgeInfo,
+    /// Used only by MBC1
+    advanced_banking: bool,
+    /// Used only by MBC1, if cartridge is a multicart that uses 4-bit ROM bank numbers
+    multicart: bool,
+    /// Used only by MBC3 cartridges with a timer
+    rtc: Option<RealTimeClock>,
+    /// Used only by MMM01
+    mmm01: Option<MMM01>,
+    /// Used only by MBC7
+    mbc7: Option<MBC7>,
+    /// Used only by HuC1, switches $A000-$BFFF between RAM and the infrared port
+    ir_mode: bool,
+    /// Used only by HuC3
+    huc3: Option<HuC3>,
+    /// Used by HuC1 and HuC3
+    #[serde(skip, default = "no_infrared")]
+    infrared: Box<dyn InfraredPort>,
+    /// Used only by Game Boy Camera
+    camera: Option<Camera>,
+    /// Used only by Game Boy Camera, provides the captured images
+    #[serde(skip, default = "test_pattern_camera")]
+    camera_source: Box<dyn CameraSource>,
+}
+
+impl MBC {
+    /// Size of the built-in RAM of MBC2 in half-bytes
+    const MBC2_RAM_SIZE: usize = 512;
+
+    pub fn init(rom: Vec<u8>, info: CartridgeInfo) -> Self {
+        let ram_size = match info.mbc {
+            MBCType::MBC2 => Self::MBC2_RAM_SIZE,
+            // The EEPROM of MBC7 is stored as RAM
+            MBCType::MBC7 => EEPROM::SIZE,
+            _ => 0x2000 * usize::from(info.ram_banks),
+        };
+        let multicart = matches!(info.mbc, MBCType::MBC1) && Self::is_mbc1_multicart(&rom);
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            advanced_banking: false,
+            multicart,
+            rtc: (info.has_timer && matches!(info.mbc, MBCType::MBC3)).then(RealTimeClock::new),
+            mbc7: matches!(info.mbc, MBCType::MBC7).then(MBC7::new),
+            mmm01: matches!(info.mbc, MBCType::MMM01).then(MMM01::new),
+            ir_mode: false,
+            huc3: matches!(info.mbc, MBCType::HuC3).then(HuC3::new),
+            infrared: no_infrared(),
+            camera: matches!(info.mbc, MBCType::PocketCamera).then(Camera::new),
+            camera_source: test_pattern_camera(),
+            info,
+        }
+    }
+
+    /// Returns if given MBC1 ROM is a multicart (MBC1M).
+    /// Multicarts contain several games with their own headers,
+    /// which are detected from the Nintendo logo of the second game at bank $10
+    fn is_mbc1_multicart(rom: &[u8]) -> bool {
+        // Multicarts are always 1 MiB
+        if rom.len() != 0x100000 {
+            return false;
+        }
+        let logo = &rom[0x0104..=0x0133];
+        let second_logo = &rom[0x10 * 0x4000 + 0x0104..=0x10 * 0x4000 + 0x0133];
+        logo == second_logo
+    }
+
+    /// Returns value from memory at address
+    /// Should handle addresses between $0000-$7FFF and $A000-$BFFF
+    pub fn read(&self, address: u16) -> u8 {
+        match self.info.mbc {
+            MBCType::NoMBC => self.read_nombc(address),
+            MBCType::MBC1 => self.read_mbc1(address),
+            MBCType::MBC2 => self.read_mbc2(address),
+            MBCType::MMM01 => self.read_mmm01(address),
+            MBCType::MBC3 => self.read_mbc3(address),
+            MBCType::MBC5 => self.read_mbc5(address),
+            MBCType::MBC7 => self.read_mbc7(address),
+            MBCType::PocketCamera => self.read_camera(address),
+            MBCType::HuC1 => self.read_huc1(address),
+            MBCType::HuC3 => self.read_huc3(address),
+            _ => todo!("MBC type {:?} not supported", self.info.mbc),
+        }
+    }
+    /// Writes value into memory or register
+    /// Should handle addresses between $0000-$7FFF and $A000-$BFFF
+    pub fn write(&mut self, address: u16, value: u8) {
+        match self.info.mbc {
+            MBCType::NoMBC => self.write_nombc(address, value),
+            MBCType::MBC1 => self.write_mbc1(address, value),
+            MBCType::MBC2 => self.write_mbc2(address, value),
+            MBCType::MMM01 => self.write_mmm01(address, value),
+            MBCType::MBC3 => self.write_mbc3(address, value),
+            MBCType::MBC5 => self.write_mbc5(address, value),
+            MBCType::MBC7 => self.write_mbc7(address, value),
+            MBCType::PocketCamera => self.write_camera(address, value),
+            MBCType::HuC1 => self.write_huc1(address, value),
+            MBCType::HuC3 => self.write_huc3(address, value),
+            _ => todo!("MBC type {:?} not supported", self.info.mbc),
+        }
+    }
+
+    fn read_rom(&self, address: usize) -> u8 {
+        if self.rom.len() <= address {
+            log::error!(
+                "Tried to read ROM at {:#06X}, but vec length is only {:#06X}",
+                address,
+                self.rom.len()
+            );
+            return 0;
+        }
+        self.rom[address]
+    }
+
+    fn read_ram(&self, address: usize) -> u8 {
+        if self.ram.len() <= address {
+            log::error!(
+                "Tried to read RAM at {:#06X}, but vec length is only {:#06X}",
+                address,
+                self.ram.len()
+            );
+            return 0;
+        }
+        self.ram[address]
+    }
+
+    fn write_ram(&mut self, address: usize, value: u8) {
+        if self.ram.len() <= address {
+            log::error!(
+                "Tried to write to RAM at {:#06X}, but vec length is only {:#06X}",
+                address,
+                self.ram.len()
+            );
+            return;
+        }
+        self.ram[address] = value;
+    }
+
+    /// Used to mask bank number register value to wrap around
+    /// based on maximum number of banks
+    fn mask_bank_number(&self, number: u8, bank_amount: u16) -> usize {
+        // Calculate amount of bits needed to contain number
+        // Clamp result to 8, when bit amount is higher the higher bits are written to another
+        // register
+        let bit_amount = (bank_amount as f32).log(2.0).ceil().min(8.0) as usize;
+        // Mask number with amount of bits
+        if bit_amount == 0 {
+            0
+        } else {
+            (number as usize) & (0xFF >> (8 - bit_amount))
+        }
+    }
+
+    fn read_nombc(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => self.read_rom(address as usize),
+            0xA000..=0xBFFF => self.read_ram(address as usize),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_nombc(&mut self, address: u16, value: u8) {
+        if matches!(address, 0xA000..=0xBFFF) {
+            self.write_ram(address as usize, value);
+        }
+    }
+
+    fn read_mbc1(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x7FFF => {
+                // If cartridge has >512 KiB ROM, the 2-bit register that is also used to select RAM banks
+                // can be used to select one of four large banks of 512 KiB memory
+                let high_bank = if self.info.rom_banks > 32 {
+                    // Mask out upper bit of high address if not enough banks
+                    let high_address = self.ram_bank
+                        & if self.info.rom_banks <= 64 && !self.multicart {
+                            0b01
+                        } else {
+                            0b11
+                        };
+                    // On multicarts the register is wired to select one of four 256 KiB games
+                    high_address << if self.multicart { 4 } else { 5 }
+                } else {
+                    0
+                };
+                // The ROM bank register is only applied to the second ROM address range ($4000-$7FFF).
+                // The 2-bit register is also applied to the first range if using advanced banking mode
+                let bank = if address >= 0x4000 {
+                    high_bank | self.rom_bank
+                } else if self.advanced_banking {
+                    high_bank
+                } else {
+                    0
+                };
+                self.read_rom(bank * 0x4000 + (address & 0x3FFF))
+            }
+            0xA000..=0xBFFF => {
+                // Reads to disabled RAM usually return 0xFF
+                if !self.ram_enabled {
+                    return 0xFF;
+                }
+                address -= 0xA000;
+                // RAM banks can only be changed when using advanced banking mode
+                if self.advanced_banking {
+                    address +=
+                        self.mask_bank_number(self.ram_bank as u8, self.info.ram_banks) * 0x2000;
+                }
+                self.read_ram(address)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_mbc1(&mut self, address: u16, value: u8) {
+        match address {
+            // Enable the RAM
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            // Less significant ROM bank register
+            0x2000..=0x3FFF => {
+                // Only needed amount of bits to change between all ROM banks
+                // are saved to the register, rest are masked out
+                let mut masked = if self.multicart {
+                    // Multicarts don't have the fifth bit wired
+                    (value & 0x0F) as usize
+                } else {
+                    self.mask_bank_number(value, self.info.rom_banks.clamp(0, 32))
+                };
+                // If register is tried to set to 0, it should be incremented to 1
+                // The check is only done for the 5-bit version for the value though,
+                // so for example if only 3 bits are used,
+                // register value 0b1000 maps the second block to ROM bank 0
+                if value & 0b1_1111 == 0 {
+                    masked += 1;
+                }
+                self.rom_bank = masked;
+            }
+            // 2 bit bank register that is used to select both ROM and RAM banks
+            0x4000..=0x5FFF => self.ram_bank = (value & 0b11) as usize,
+            // Toggle between banking modes
+            // The above register only has effect if this is set to true
+            0x6000..=0x7FFF => self.advanced_banking = value & 0b1 > 0,
+            // Write to RAM
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return;
+                }
+                let mut address = address as usize;
+                address -= 0xA000;
+                // RAM banks can only be changed when using advanced banking mode
+                if self.advanced_banking && self.info.ram_banks > 1 {
+                    address += self.ram_bank * 0x2000;
+                }
+                self.write_ram(address, value);
+            }
+            _ => {}
+        };
+    }
+
+    fn read_mbc2(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.read_rom(address),
+            0x4000..=0x7FFF => {
+                address -= 0x4000;
+                address += self.rom_bank * 0x4000;
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return 0xFF;
+                }
+                // Only the bottom 9 bits of the address are used,
+                // so the RAM is echoed through the whole address range.
+                // RAM only stores half-bytes, so the upper bits are read as 1s
+                self.read_ram(address & 0x1FF) | 0xF0
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_mbc2(&mut self, address: u16, value: u8) {
+        match address {
+            // Bit 8 of the address selects which register is written to
+            0x0000..=0x3FFF => {
+                if address & 0x0100 == 0 {
+                    // Enable the RAM
+                    self.ram_enabled = (value & 0x0F) == 0x0A;
+                } else {
+                    // ROM bank number, where 0 is incremented to 1
+                    let mut masked = self.mask_bank_number(value & 0x0F, self.info.rom_banks);
+                    if value & 0x0F == 0 {
+                        masked += 1;
+                    }
+                    self.rom_bank = masked;
+                }
+            }
+            // Write to RAM
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return;
+                }
+                self.write_ram((address & 0x1FF) as usize, value & 0x0F);
+            }
+            _ => {}
+        };
+    }
+
+    fn read_mbc3(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.rom[address],
+            0x4000..=0x7FFF => {
+                address += 0x4000 * (self.rom_bank - 1);
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return 0xFF;
+                }
+                // Bank numbers $08-$0C map a clock register to the whole RAM range
+                if let Some(rtc) = &self.rtc
+                    && self.ram_bank >= 0x08
+                {
+                    return rtc.read(self.ram_bank as u8);
+                }
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.read_ram(address)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_mbc3(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enabled
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            // ROM bank number
+            0x2000..=0x3FFF => {
+                let mut masked = self.mask_bank_number(value, self.info.rom_banks);
+                if masked == 0 {
+                    masked = 1
+                };
+                self.rom_bank = masked;
+            }
+            // RAM bank number or clock register select
+            0x4000..=0x5FFF => {
+                if self.rtc.is_some() && matches!(value, 0x08..=0x0C) {
+                    self.ram_bank = value as usize;
+                } else if self.info.ram_banks != 0 {
+                    self.ram_bank = self.mask_bank_number(value, self.info.ram_banks);
+                }
+            }
+            // Latch clock data
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+            // Write to RAM or clock register
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return;
+                }
+                if let Some(rtc) = &mut self.rtc
+                    && self.ram_bank >= 0x08
+                {
+                    rtc.write(self.ram_bank as u8, value);
+                    return;
+                }
+                let mut address = address as usize;
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.write_ram(address, value);
+            }
+            _ => {}
+        };
+    }
+
+    fn read_mbc5(&self, address: u16) -> u8 {
+        let mut address = address as usize;
+        match address {
+            0x0000..=0x3FFF => self.rom[address],
+            0x4000..=0x7FFF => {
+                address = address.saturating_add_signed(0x4000 * ((self.rom_bank as isize) - 1));
+                self.read_rom(address)
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return 0xFF;
+                }
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.read_ram(address)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_mbc5(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enabled
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            // 8 least significant bits of ROM bank number
+            0x2000..=0x2FFF => {
+                let masked = self.mask_bank_number(value, self.info.rom_banks);
+                self.rom_bank = masked;
+            }
+            // 9th bit of ROM bank number
+            0x3000..=0x3FFF => self.rom_bank |= ((value & 1) as usize) << 8,
+            // RAM bank number
+            0x4000..=0x5FFF if self.info.ram_banks != 0 => {
+                self.ram_bank = self.mask_bank_number(value, self.info.ram_banks);
+            }
+            // Write to RAM
+            0xA000..=0xBFFF => {
+                if !self.ram_enabled {
+                    return;
+                }
+                let mut address = address as usize;
+                address -= 0xA000;
+                address += self.ram_bank * 0x2000;
+                self.write_ram(address, value);
+            }
+            _ => {}
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a 1 MiB MBC1 multicart of four games, where each ROM bank starts with its number
+    fn mbc1_multicart() -> MBC {
+        let mut rom = vec![0; 0x100000];
+        for bank in 0..64 {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        for game in 0..4 {
+            let header = game * 0x10 * 0x4000 + 0x0100;
+            rom[header + 0x04..header + 0x34].fill(0xCE);
+            rom[header + 0x47] = 0x01;
+            rom[header + 0x48] = 0x05;
+        }
+        let info = CartridgeInfo::from_header(&rom[0x0100..=0x014F]);
+        MBC::init(rom, info)
+    }
+
+    #[test]
+    fn mbc1_multicart_selects_game_banks() {
+        let mut mbc = mbc1_multicart();
+        assert!(mbc.multicart);
+        for game in 0..4 {
+            mbc.write(0x4000, game);
+            // The fifth bit of the ROM bank isn't wired, but still counts when checking for bank 0
+            for (value, bank) in [(0x10, 0), (0x20, 1), (0x30, 0)] {
+                mbc.write(0x2000, value);
+                assert_eq!(
+                    mbc.read(0x4000),
+                    game * 0x10 + bank,
+                    "game {game}, ${value:02X}"
+                );
+            }
+            // The first range only follows the selected game in advanced banking mode
+            assert_eq!(mbc.read(0x0000), 0);
+            mbc.write(0x6000, 1);
+            assert_eq!(mbc.read(0x0000), game * 0x10);
+            mbc.write(0x6000, 0);
+        }
+    }
+}
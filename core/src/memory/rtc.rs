@@ -0,0 +1,222 @@
+use super::*;
+
+/// The clock registers of the MBC3 real-time clock
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+pub struct ClockRegisters {
+    /// Seconds (0-59)
+    pub seconds: u8,
+    /// Minutes (0-59)
+    pub minutes: u8,
+    /// Hours (0-23)
+    pub hours: u8,
+    /// Lower 8 bits of the day counter
+    pub day_low: u8,
+    /// Bit 0: Upper bit of the day counter,
+    /// bit 6: Halt flag,
+    /// bit 7: Day counter carry flag
+    pub day_high: u8,
+}
+
+impl ClockRegisters {
+    const HALT: u8 = 0b0100_0000;
+    const DAY_CARRY: u8 = 0b1000_0000;
+
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.day_low,
+            0x0C => self.day_high,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        // Only the bits that exist in hardware are stored
+        match register {
+            0x08 => self.seconds = value & 0x3F,
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.day_low = value,
+            0x0C => self.day_high = value & 0xC1,
+            _ => {}
+        }
+    }
+
+    fn days(&self) -> u16 {
+        (((self.day_high & 1) as u16) << 8) | self.day_low as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        self.day_low = days as u8;
+        self.day_high = (self.day_high & !1) | ((days >> 8) as u8 & 1);
+    }
+
+    /// Increments the clock by one second
+    fn tick(&mut self) {
+        // Counters only wrap around when reaching their normal maximum value.
+        // Out of range values written by software keep counting until the register overflows,
+        // without carrying to the next counter
+        if self.seconds == 59 {
+            self.seconds = 0;
+        } else {
+            self.seconds = (self.seconds + 1) & 0x3F;
+            return;
+        }
+        if self.minutes == 59 {
+            self.minutes = 0;
+        } else {
+            self.minutes = (self.minutes + 1) & 0x3F;
+            return;
+        }
+        if self.hours == 23 {
+            self.hours = 0;
+        } else {
+            self.hours = (self.hours + 1) & 0x1F;
+            return;
+        }
+        let days = self.days();
+        if days == 0x1FF {
+            self.set_days(0);
+            self.day_high |= Self::DAY_CARRY;
+        } else {
+            self.set_days(days + 1);
+        }
+    }
+
+    /// Increments the clock by given amount of seconds
+    fn advance(&mut self, mut seconds: u64) {
+        // Tick one second at a time until the time counters are back in their normal ranges
+        while seconds > 0 && (self.seconds > 59 || self.minutes > 59 || self.hours > 23) {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+        let time = (self.seconds as u64) + (self.minutes as u64) * 60 + (self.hours as u64) * 3600;
+        let total = time + (self.days() as u64) * 86400 + seconds;
+        let days = total / 86400;
+        let time = total % 86400;
+        self.seconds = (time % 60) as u8;
+        self.minutes = ((time / 60) % 60) as u8;
+        self.hours = (time / 3600) as u8;
+        if days > 0x1FF {
+            self.day_high |= Self::DAY_CARRY;
+        }
+        self.set_days((days % 0x200) as u16);
+    }
+}
+
+/// Simulates the real-time clock found on some MBC3 cartridges
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct RealTimeClock {
+    /// The running clock registers
+    pub registers: ClockRegisters,
+    /// Copy of the clock registers made when latching, which are the ones read by the CPU
+    pub latched: ClockRegisters,
+    /// If $00 was last written to the latch register,
+    /// meaning that writing $01 next latches the clock
+    latch_armed: bool,
+    /// T-cycles elapsed since the last second
+    subsecond_cycles: u32,
+    /// Host Unix timestamp in seconds that corresponds to the current clock state.
+    /// Zero if the clock hasn't been synchronized yet
+    pub timestamp: u64,
+}
+
+impl RealTimeClock {
+    const CYCLES_PER_SECOND: u32 = 4194304;
+    /// Size of the clock data appended to save RAM,
+    /// in the format used by most other emulators
+    pub const SAVE_SIZE: usize = 48;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cycles the clock forward by one T-cycle
+    pub fn cycle(&mut self) {
+        if self.registers.day_high & ClockRegisters::HALT > 0 {
+            return;
+        }
+        self.subsecond_cycles += 1;
+        if self.subsecond_cycles >= Self::CYCLES_PER_SECOND {
+            self.subsecond_cycles = 0;
+            self.registers.tick();
+            if self.timestamp != 0 {
+                self.timestamp += 1;
+            }
+        }
+    }
+
+    /// Advances the clock to match given host Unix timestamp
+    pub fn sync(&mut self, timestamp: u64) {
+        // The first synchronization only sets the reference point
+        if self.timestamp != 0
+            && timestamp > self.timestamp
+            && self.registers.day_high & ClockRegisters::HALT == 0
+        {
+            self.registers.advance(timestamp - self.timestamp);
+        }
+        self.timestamp = self.timestamp.max(timestamp);
+    }
+
+    /// Returns value of given clock register ($08-$0C)
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    /// Writes value to given clock register ($08-$0C)
+    pub fn write(&mut self, register: u8, value: u8) {
+        // Writing to the seconds register resets the subsecond divider
+        if register == 0x08 {
+            self.subsecond_cycles = 0;
+        }
+        self.registers.write(register, value);
+        self.latched.write(register, value);
+    }
+
+    /// Handles write to the latch register,
+    /// which copies the running clock to the readable registers when $00 and $01 are written in sequence
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.latched = self.registers;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Serializes the clock state into the 48-byte format that is appended to save RAM
+    pub fn to_save(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::SAVE_SIZE);
+        for registers in [&self.registers, &self.latched] {
+            for register in 0x08..=0x0C {
+                data.extend_from_slice(&(registers.read(register) as u32).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data
+    }
+
+    /// Restores the clock state from data appended to save RAM.
+    /// Also accepts the older 44-byte format that has a 32-bit timestamp
+    pub fn load_save(&mut self, data: &[u8]) {
+        if data.len() < 44 {
+            log::error!("Clock save data is only {} bytes long", data.len());
+            return;
+        }
+        let word = |i: usize| data[i * 4];
+        for register in 0x08..=0x0C {
+            let i = (register - 0x08) as usize;
+            self.registers.write(register, word(i));
+            self.latched.write(register, word(i + 5));
+        }
+        self.timestamp = if data.len() >= Self::SAVE_SIZE {
+            u64::from_le_bytes(data[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64
+        };
+        self.subsecond_cycles = 0;
+    }
+}
@@ -0,0 +1,289 @@
+use super::*;
+
+/// Game Boy hardware model, which affects the state the boot ROM leaves the system in
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Model {
+    /// Original Game Boy with the early boot ROM
+    DMG0,
+    /// Original Game Boy
+    #[default]
+    DMG,
+    /// Game Boy Pocket
+    MGB,
+    /// Super Game Boy
+    SGB,
+    /// Super Game Boy 2
+    SGB2,
+    /// Game Boy Color
+    CGB,
+}
+
+impl std::str::FromStr for Model {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "DMG0" => Ok(Model::DMG0),
+            "DMG" => Ok(Model::DMG),
+            "MGB" => Ok(Model::MGB),
+            "SGB" => Ok(Model::SGB),
+            "SGB2" => Ok(Model::SGB2),
+            "CGB" => Ok(Model::CGB),
+            _ => Err(format!("Unknown Game Boy model {name}")),
+        }
+    }
+}
+
+/// State of the system after the boot ROM has finished
+pub(crate) struct PostBootState {
+    pub registers: Registers,
+    /// The internal 16-bit divider counter
+    pub div: u16,
+    /// Scanline the PPU is on
+    pub ly: u8,
+    /// Dot the PPU is at on the scanline
+    pub lx: u16,
+    /// Value of the joypad register P1
+    pub p1: u8,
+    /// Values written to I/O registers by the boot ROM, in order
+    pub io: &'static [(u16, u8)],
+    /// If the boot ROM played the startup chime, which leaves channel 1 on at volume 0
+    pub chime: bool,
+}
+
+/// I/O register values the DMG, MGB and SGB boot ROMs leave behind, written in order.
+/// Trigger bits of NRx4 are left clear, so the startup chime isn't played again.
+/// P1, LY, DMA and wave RAM aren't written, as they are set up separately or left uninitialized
+const DMG_IO: &[(u16, u8)] = &[
+    // SB, SC
+    (0xFF01, 0x00),
+    (0xFF02, 0x7E),
+    // TIMA, TMA, TAC
+    (0xFF05, 0x00),
+    (0xFF06, 0x00),
+    (0xFF07, 0xF8),
+    // NR52 first, as the other sound registers can't be written while the APU is off
+    (0xFF26, 0x80),
+    // NR10, NR11, NR12, NR13, NR14
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF13, 0xFF),
+    (0xFF14, 0x3F),
+    // NR21, NR22, NR23, NR24
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF18, 0xFF),
+    (0xFF19, 0x3F),
+    // NR30, NR31, NR32, NR33, NR34
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF),
+    (0xFF1E, 0x3F),
+    // NR41, NR42, NR43, NR44
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0x3F),
+    // NR50, NR51
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    // LCDC, STAT, SCY, SCX, LYC
+    (0xFF40, 0x91),
+    (0xFF41, 0x85),
+    (0xFF42, 0x00),
+    (0xFF43, 0x00),
+    (0xFF45, 0x00),
+    // BGP, OBP0, OBP1: object palettes are left uninitialized
+    (0xFF47, 0xFC),
+    (0xFF48, 0xFF),
+    (0xFF49, 0xFF),
+    // WY, WX
+    (0xFF4A, 0x00),
+    (0xFF4B, 0x00),
+    // IE, and IF last so that only VBlank is pending
+    (0xFFFF, 0x00),
+    (0xFF0F, 0xE1),
+];
+
+/// I/O register values the Game Boy Color boot ROM leaves behind, written in order.
+/// Registers only mapped in color mode are ignored when running in DMG compatibility mode.
+/// HDMA5 isn't written, as that would start a transfer, and neither is the unemulated
+/// infrared port
+const CGB_IO: &[(u16, u8)] = &[
+    // SB, SC
+    (0xFF01, 0x00),
+    (0xFF02, 0x7F),
+    // TIMA, TMA, TAC
+    (0xFF05, 0x00),
+    (0xFF06, 0x00),
+    (0xFF07, 0xF8),
+    // NR52 first, as the other sound registers can't be written while the APU is off
+    (0xFF26, 0x80),
+    // NR10, NR11, NR12, NR13, NR14
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF13, 0xFF),
+    (0xFF14, 0x3F),
+    // NR21, NR22, NR23, NR24
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF18, 0xFF),
+    (0xFF19, 0x3F),
+    // NR30, NR31, NR32, NR33, NR34
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF),
+    (0xFF1E, 0x3F),
+    // NR41, NR42, NR43, NR44
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0x3F),
+    // NR50, NR51
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    // LCDC, STAT, SCY, SCX, LYC
+    (0xFF40, 0x91),
+    (0xFF41, 0x85),
+    (0xFF42, 0x00),
+    (0xFF43, 0x00),
+    (0xFF45, 0x00),
+    // BGP, OBP0, OBP1
+    (0xFF47, 0xFC),
+    (0xFF48, 0x00),
+    (0xFF49, 0x00),
+    // WY, WX
+    (0xFF4A, 0x00),
+    (0xFF4B, 0x00),
+    // KEY1: normal speed without a pending switch
+    (0xFF4D, 0x00),
+    // VBK: VRAM bank 0
+    (0xFF4F, 0x00),
+    // HDMA1, HDMA2, HDMA3, HDMA4
+    (0xFF51, 0xFF),
+    (0xFF52, 0xFF),
+    (0xFF53, 0xFF),
+    (0xFF54, 0xFF),
+    // BCPS, OCPS
+    (0xFF68, 0x00),
+    (0xFF6A, 0x00),
+    // OPRI: objects are prioritized by OAM index
+    (0xFF6C, 0x00),
+    // SVBK: WRAM bank 1
+    (0xFF70, 0x00),
+    // IE, and IF last so that only VBlank is pending
+    (0xFFFF, 0x00),
+    (0xFF0F, 0xE1),
+];
+
+impl Model {
+    /// Returns the model best suited for given cartridge: CGB for cartridges that support color,
+    /// SGB for cartridges that only enhance Super Game Boy, and DMG for the rest
+    pub fn for_cartridge(info: &CartridgeInfo) -> Self {
+        if info.cgb_support {
+            Model::CGB
+        } else if info.sgb_support {
+            Model::SGB
+        } else {
+            Model::DMG
+        }
+    }
+
+    /// Returns the state the boot ROM of this model leaves the system in.
+    /// On DMG and MGB the flags depend on the header checksum of the cartridge,
+    /// and on CGB the registers depend on if the cartridge runs in color mode
+    pub(crate) fn post_boot_state(&self, header_checksum: u8, cgb_mode: bool) -> PostBootState {
+        let registers = |a, f, b, c, d, e, h, l| Registers {
+            a,
+            f: FlagReg::from_bits_truncate(f),
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            sp: 0xFFFE,
+            pc: 0x0100,
+        };
+        // Carry and half-carry are set unless the header checksum is 0
+        let flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+        match self {
+            // The early boot ROM finishes during the second line of VBlank
+            Model::DMG0 => PostBootState {
+                registers: registers(0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03),
+                div: 0x1830,
+                ly: 145,
+                lx: 100,
+                p1: 0xCF,
+                io: DMG_IO,
+                chime: true,
+            },
+            // Boot ROM finishes on the last line of VBlank, where LY already reads 0
+            Model::DMG => PostBootState {
+                registers: registers(0x01, flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xCF,
+                io: DMG_IO,
+                chime: true,
+            },
+            Model::MGB => PostBootState {
+                registers: registers(0xFF, flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xCF,
+                io: DMG_IO,
+                chime: true,
+            },
+            // Boot duration varies with the communication with the SNES,
+            // so the divider and PPU position are the same as on DMG.
+            // The joypad lines were used to send the header to the SNES, and are left deselected
+            // since selecting both starts a packet transfer. No chime is played by the Game Boy
+            Model::SGB => PostBootState {
+                registers: registers(0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xFF,
+                io: DMG_IO,
+                chime: false,
+            },
+            Model::SGB2 => PostBootState {
+                registers: registers(0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xFF,
+                io: DMG_IO,
+                chime: false,
+            },
+            // Boot duration varies with the logo animation,
+            // so the divider and PPU position are the same as on DMG
+            Model::CGB if cgb_mode => PostBootState {
+                registers: registers(0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xCF,
+                io: CGB_IO,
+                chime: true,
+            },
+            Model::CGB => PostBootState {
+                registers: registers(0x11, 0x80, 0x00, 0x00, 0x00, 0x08, 0x00, 0x7C),
+                div: 0xABCC,
+                ly: 153,
+                lx: 400,
+                p1: 0xCF,
+                io: CGB_IO,
+                chime: true,
+            },
+        }
+    }
+}
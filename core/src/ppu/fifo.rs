@@ -0,0 +1,452 @@
+use super::*;
+
+/// Step of the background / window tile fetcher, each taking two dots
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum FetchStep {
+    /// Read tile index from the tile map
+    Tile,
+    /// Read the low byte of the tile row
+    DataLow,
+    /// Read the high byte of the tile row
+    DataHigh,
+    /// Wait for the background FIFO to be empty so the row can be pushed to it
+    Push,
+}
+
+/// Fetches background and window tile rows into the background FIFO
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct Fetcher {
+    pub step: FetchStep,
+    /// Dots spent in the current step
+    dots: u8,
+    /// Column of the tile being fetched, counted from the start of the line or window
+    x: u8,
+    /// If fetching window tiles instead of background tiles
+    pub window: bool,
+    tile_index: u8,
+    /// Attributes of the tile being fetched, only used in CGB mode
+    attributes: TileAttributes,
+    data_low: u8,
+    data_high: u8,
+}
+
+impl Fetcher {
+    pub fn new(window: bool) -> Self {
+        Self {
+            step: FetchStep::Tile,
+            dots: 0,
+            x: 0,
+            window,
+            tile_index: 0,
+            attributes: TileAttributes::empty(),
+            data_low: 0,
+            data_high: 0,
+        }
+    }
+}
+
+/// Pixel in the background FIFO
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct BackgroundPixel {
+    /// Color ID
+    color: u8,
+    /// Color palette, only used in CGB mode
+    palette: u8,
+    /// If colors 1-3 are drawn over objects, only used in CGB mode
+    priority: bool,
+}
+
+/// Pixel in the object FIFO
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct ObjectPixel {
+    /// Color ID, where 0 is transparent
+    color: u8,
+    /// Palette used, which is OBP0 or OBP1 on DMG and one of the color palettes in CGB mode
+    palette: u8,
+    /// If background and window colors 1-3 are drawn over the object
+    priority: bool,
+    /// Index of the object in OAM, which decides priority between objects in CGB mode
+    oam_index: u8,
+}
+
+/// Object on the current scanline along with its index in OAM
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct LineSprite {
+    oam_index: u8,
+    sprite: OAMSprite,
+}
+
+/// Object being fetched, which stops pixels from being shifted out
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct ObjectFetch {
+    object: LineSprite,
+    /// Dots spent fetching, the object row is ready once the background fetcher is done too
+    dots: u8,
+}
+
+impl PPU {
+    /// Dots spent fetching the first tile of a line, which is thrown away
+    const STARTUP_DOTS: u8 = 6;
+    /// Dots spent fetching an object
+    const OBJECT_FETCH_DOTS: u8 = 6;
+
+    fn object_height(&self) -> u8 {
+        if self.lcdc.intersects(LCDControl::OBJ_SIZE) {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Selects the objects on the current scanline, done during OAM scan
+    fn scan_oam(&self) -> Vec<LineSprite> {
+        // OAM can't be read while OAM DMA is writing to it
+        if self.oam_dma_active {
+            return Vec::new();
+        }
+        // Convert screen Y to object space,
+        // where y = 0 completely hides the object
+        let obj_y = self.ly as u16 + 16;
+        let height = self.object_height() as u16;
+        self.oam
+            .sprites
+            .iter()
+            .zip(0..)
+            .filter(|(sprite, _)| obj_y >= sprite.y as u16 && obj_y < sprite.y as u16 + height)
+            // There's a limit of 10 objects per scanline
+            .take(10)
+            .map(|(&sprite, oam_index)| LineSprite { oam_index, sprite })
+            .collect()
+    }
+
+    /// Prepares the FIFOs and fetchers for drawing the current scanline
+    pub(super) fn start_drawing(&mut self) {
+        // Window can be drawn from the scanline WY matches until the end of the frame
+        if self.ly == self.win_y {
+            self.window_y_reached = true;
+        }
+        self.line_sprites = self.scan_oam();
+        self.bg_fifo.clear();
+        self.obj_fifo.clear();
+        self.fetcher = Fetcher::new(false);
+        self.object_fetch = None;
+        self.lcd_x = 0;
+        // Pixels scrolled out of view are shifted out before drawing the first pixel
+        self.discard = self.bg_x % 8;
+        self.startup_dots = Self::STARTUP_DOTS;
+        self.window_on_line = false;
+    }
+
+    /// Finishes drawing the current scanline
+    pub(super) fn finish_drawing(&mut self) {
+        // Window line counter only increments on lines the window was drawn on
+        if self.window_on_line {
+            self.win_line = self.win_line.wrapping_add(1);
+        }
+    }
+
+    /// Runs the pixel pipeline for one dot of mode 3
+    pub(super) fn draw_dot(&mut self) {
+        if self.startup_dots > 0 {
+            self.startup_dots -= 1;
+            return;
+        }
+
+        // Window restarts the fetcher once its left edge is reached
+        if !self.fetcher.window && self.window_starts() {
+            self.bg_fifo.clear();
+            self.fetcher = Fetcher::new(true);
+            self.window_on_line = true;
+            // Window at WX < 7 is partially shifted off the left edge
+            self.discard = 7u8.saturating_sub(self.win_x);
+        }
+
+        // Objects are fetched once their left edge is reached
+        if self.object_fetch.is_none()
+            && let Some(i) = self.next_object()
+        {
+            let object = self.line_sprites.remove(i);
+            self.object_fetch = Some(ObjectFetch { object, dots: 0 });
+        }
+
+        if let Some(mut fetch) = self.object_fetch {
+            // The background fetcher finishes fetching its tile before the object is fetched,
+            // and the object fetch starts on the same dot the tile is finished
+            if self.fetcher.step != FetchStep::Push {
+                self.step_fetcher();
+            }
+            if self.fetcher.step == FetchStep::Push {
+                fetch.dots += 1;
+            }
+            if fetch.dots < Self::OBJECT_FETCH_DOTS {
+                self.object_fetch = Some(fetch);
+                return;
+            }
+            self.fetch_object(fetch.object);
+            self.object_fetch = None;
+            // Pixels are shifted out again on the dot the object is mixed in,
+            // unless another object starts at the same pixel
+            if self.next_object().is_some() {
+                return;
+            }
+        } else {
+            self.step_fetcher();
+        }
+
+        // Shift out one pixel
+        let Some(bg) = self.bg_fifo.pop_front() else {
+            return;
+        };
+        if self.discard > 0 {
+            self.discard -= 1;
+            return;
+        }
+        let object = self.obj_fifo.pop_front();
+        if self.cgb {
+            let color = self.mix_color_pixel(bg, object);
+            self.set_color_pixel(self.lcd_x, self.ly, color);
+            self.lcd_x += 1;
+            return;
+        }
+        let bg_color = if self.lcdc.intersects(LCDControl::BG_WINDOW_ENABLE) {
+            bg.color
+        } else {
+            0
+        };
+        let color = match object {
+            // Object is drawn if it isn't transparent and background isn't prioritized over it
+            Some(object)
+                if object.color != 0
+                    && self.lcdc.intersects(LCDControl::OBJ_ENABLE)
+                    && !(object.priority && bg_color != 0) =>
+            {
+                let palette = if object.palette == 1 {
+                    self.palettes.obj1
+                } else {
+                    self.palettes.obj0
+                };
+                self.get_palette_color(object.color, palette)
+            }
+            _ => self.get_palette_color(bg_color, self.palettes.bg),
+        };
+        self.set_pixel(self.lcd_x, self.ly, color);
+        self.lcd_x += 1;
+    }
+
+    /// Returns the color of a pixel in CGB mode, where LCDC bit 0 only decides
+    /// if the background and window can be drawn over objects
+    fn mix_color_pixel(&self, bg: BackgroundPixel, object: Option<ObjectPixel>) -> u16 {
+        let bg_priority = self.lcdc.intersects(LCDControl::BG_WINDOW_ENABLE) && bg.color != 0;
+        match object {
+            Some(object)
+                if object.color != 0
+                    && self.lcdc.intersects(LCDControl::OBJ_ENABLE)
+                    && !(bg_priority && (bg.priority || object.priority)) =>
+            {
+                CGBPalettes::color(&self.color_palettes.obj, object.palette, object.color)
+            }
+            _ => CGBPalettes::color(&self.color_palettes.bg, bg.palette, bg.color),
+        }
+    }
+
+    /// Returns the index of the next object to fetch, if one starts at the current pixel
+    fn next_object(&self) -> Option<usize> {
+        if !self.lcdc.intersects(LCDControl::OBJ_ENABLE) {
+            return None;
+        }
+        let mut started = self
+            .line_sprites
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| object.sprite.x <= self.lcd_x + 8);
+        if self.oam_object_priority() {
+            started.next().map(|(i, _)| i)
+        } else {
+            // Objects fetched earlier keep priority, so the object with the lowest X is fetched
+            // first, with ties broken by OAM order
+            started
+                .min_by_key(|(_, object)| (object.sprite.x, object.oam_index))
+                .map(|(i, _)| i)
+        }
+    }
+
+    /// Returns if objects earlier in OAM have priority over other objects,
+    /// instead of objects further left, which only happens in CGB mode
+    fn oam_object_priority(&self) -> bool {
+        self.cgb && !self.dmg_object_priority
+    }
+
+    /// Returns if the window should start at the current pixel
+    fn window_starts(&self) -> bool {
+        if !self.lcdc.intersects(LCDControl::WINDOW_ENABLE) || !self.window_y_reached {
+            return false;
+        }
+        // Window starts where WX - 7 matches the pixel, or at the first pixel when WX < 7
+        self.lcd_x as u16 + 7 == self.win_x as u16 || (self.lcd_x == 0 && self.win_x < 7)
+    }
+
+    /// Advances the background fetcher by one dot
+    fn step_fetcher(&mut self) {
+        use FetchStep::*;
+        if self.fetcher.step != Push {
+            self.fetcher.dots += 1;
+            if self.fetcher.dots < 2 {
+                return;
+            }
+            self.fetcher.dots = 0;
+        }
+        match self.fetcher.step {
+            Tile => {
+                let tile_map_address = self.tile_map_address();
+                self.fetcher.tile_index = self.vram[tile_map_address];
+                // Attributes are found at the same address in VRAM bank 1
+                if self.cgb {
+                    self.fetcher.attributes =
+                        TileAttributes::from_bits_retain(self.vram[0x2000 + tile_map_address]);
+                }
+                self.fetcher.step = DataLow;
+            }
+            DataLow => {
+                self.fetcher.data_low = self.vram[self.tile_row_address()];
+                self.fetcher.step = DataHigh;
+            }
+            DataHigh => {
+                self.fetcher.data_high = self.vram[self.tile_row_address() + 1];
+                self.fetcher.step = Push;
+                // The row is pushed right away if the FIFO is empty
+                self.push_tile_row();
+            }
+            Push => self.push_tile_row(),
+        }
+    }
+
+    /// Pushes the fetched tile row into the background FIFO if it's empty
+    fn push_tile_row(&mut self) {
+        if !self.bg_fifo.is_empty() {
+            return;
+        }
+        let attributes = self.fetcher.attributes;
+        for i in 0..8 {
+            let bit = if attributes.intersects(TileAttributes::X_FLIP) {
+                i
+            } else {
+                7 - i
+            };
+            let low = (self.fetcher.data_low >> bit) & 1;
+            let high = (self.fetcher.data_high >> bit) & 1;
+            self.bg_fifo.push_back(BackgroundPixel {
+                color: (high << 1) | low,
+                palette: (attributes & TileAttributes::PALETTE).bits(),
+                priority: attributes.intersects(TileAttributes::PRIORITY),
+            });
+        }
+        self.fetcher.x = self.fetcher.x.wrapping_add(1);
+        self.fetcher.step = FetchStep::Tile;
+    }
+
+    /// Returns VRAM address of the tile map entry of the tile being fetched
+    fn tile_map_address(&self) -> usize {
+        let (tile_map, x, y) = if self.fetcher.window {
+            (
+                self.lcdc.intersects(LCDControl::WINDOW_TILE_MAP),
+                self.fetcher.x,
+                self.win_line,
+            )
+        } else {
+            // Coarse scroll is read again for every tile, so it can be changed mid-line
+            (
+                self.lcdc.intersects(LCDControl::BG_TILE_MAP),
+                (self.bg_x / 8).wrapping_add(self.fetcher.x),
+                self.ly.wrapping_add(self.bg_y),
+            )
+        };
+        let tile_map_root: usize = if tile_map { 0x1C00 } else { 0x1800 };
+        let tile_map_index = (y as usize / 8) * 32 + (x as usize % 32);
+        tile_map_root + tile_map_index
+    }
+
+    /// Returns VRAM address of the low byte of the tile row being fetched
+    fn tile_row_address(&self) -> usize {
+        let y = if self.fetcher.window {
+            self.win_line
+        } else {
+            self.ly.wrapping_add(self.bg_y)
+        };
+        let tile_index = self.fetcher.tile_index;
+        let attributes = self.fetcher.attributes;
+        let row = if attributes.intersects(TileAttributes::Y_FLIP) {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+        // One tile is 16 bytes and each row is two bytes
+        let mut address = 16 * (tile_index as usize) + 2 * (row as usize);
+        if attributes.intersects(TileAttributes::BANK) {
+            address += 0x2000;
+        }
+        // When using alternative addressing mode, tile data at index < 128
+        // are found at address 0x9000-0x97FF
+        if !self.lcdc.intersects(LCDControl::TILE_DATA_AREA) && tile_index < 128 {
+            address += 0x1000;
+        }
+        address
+    }
+
+    /// Fetches the row of given object on the current scanline and mixes it into the object FIFO
+    fn fetch_object(&mut self, object: LineSprite) {
+        let sprite = object.sprite;
+        let height = self.object_height();
+        let mut row = (self.ly + 16).wrapping_sub(sprite.y) % height;
+        if sprite.flags.intersects(SpriteFlags::Y_FLIP) {
+            row = height - 1 - row;
+        }
+        let mut tile_index = sprite.tile_index;
+        // 8x16 objects ignore last bit of tile index,
+        // so the bottom half is read from the next tile
+        if height == 16 {
+            tile_index &= 0b1111_1110;
+        }
+        let mut address = 16 * (tile_index as usize) + 2 * (row as usize);
+        let palette = if self.cgb {
+            if sprite.flags.intersects(SpriteFlags::BANK) {
+                address += 0x2000;
+            }
+            (sprite.flags & SpriteFlags::CGB_PALETTE).bits()
+        } else {
+            sprite.flags.intersects(SpriteFlags::PALETTE) as u8
+        };
+        let data_low = self.vram[address];
+        let data_high = self.vram[address + 1];
+
+        // Pixels left of the current pixel have already been drawn or are off screen
+        let skip = (self.lcd_x + 8).saturating_sub(sprite.x) as usize;
+        for i in skip..8 {
+            let bit = if sprite.flags.intersects(SpriteFlags::X_FLIP) {
+                i
+            } else {
+                7 - i
+            };
+            let pixel = ObjectPixel {
+                color: (((data_high >> bit) & 1) << 1) | ((data_low >> bit) & 1),
+                palette,
+                priority: sprite.flags.intersects(SpriteFlags::PRIORITY),
+                oam_index: object.oam_index,
+            };
+            // Objects fetched earlier keep priority over later ones where they aren't transparent.
+            // In CGB mode objects earlier in OAM have priority instead
+            let oam_priority = self.oam_object_priority();
+            match self.obj_fifo.get_mut(i - skip) {
+                Some(existing)
+                    if existing.color == 0
+                        || (oam_priority
+                            && pixel.color != 0
+                            && pixel.oam_index < existing.oam_index) =>
+                {
+                    *existing = pixel
+                }
+                Some(_) => {}
+                None => self.obj_fifo.push_back(pixel),
+            }
+        }
+    }
+}
@@ -0,0 +1,739 @@
+use super::*;
+use double_buffer::DoubleBuffer;
+use fifo::*;
+use std::{cell::Cell, collections::VecDeque};
+
+mod fifo;
+
+/// LCDC register
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct LCDControl(u8);
+
+/// Determines which sources to use for STAT interrupt
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct STATEnable(u8);
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct SpriteFlags(u8);
+
+/// Attributes of a background or window tile, stored in VRAM bank 1 on CGB
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct TileAttributes(u8);
+
+bitflags! {
+    impl LCDControl: u8 {
+        /// If PPU is enabled
+        const ENABLE              = 0b1000_0000;
+        /// Chooses between two tile map areas to use for drawing window
+        const WINDOW_TILE_MAP     = 0b0100_0000;
+        /// If window should be drawn
+        const WINDOW_ENABLE       = 0b0010_0000;
+        /// Chooses between two tile data areas to use for drawing
+        /// window and background
+        const TILE_DATA_AREA      = 0b0001_0000;
+        /// Chooses between two tile map areas to use for drawing background
+        const BG_TILE_MAP         = 0b0000_1000;
+        /// Chooses whether to use larger sized objects (8x16) instead of regular ones (8x8)
+        const OBJ_SIZE            = 0b0000_0100;
+        /// If objects should be drawn
+        const OBJ_ENABLE          = 0b0000_0010;
+        /// Disables both background and window when 0
+        const BG_WINDOW_ENABLE    = 0b0000_0001;
+    }
+
+    impl STATEnable: u8 {
+        // When scanline hits value in LYC register
+        const LYC   = 0b0100_0000;
+        // When PPU starts drawing new line
+        const Mode2 = 0b0010_0000;
+        // When VBlank is hit and frame is fully drawn
+        const Mode1 = 0b0001_0000;
+        // When HBlank is hit and scanline is fully drawn
+        const Mode0 = 0b0000_1000;
+    }
+
+    impl SpriteFlags: u8 {
+        /// If true, background and window is drawn over sprite
+        const PRIORITY = 0b1000_0000;
+        /// If sprite should be flipped vertically
+        const Y_FLIP   = 0b0100_0000;
+        /// If sprite should be flipped horizontally
+        const X_FLIP   = 0b0010_0000;
+        /// Which palette to use
+        const PALETTE  = 0b0001_0000;
+        /// Which VRAM bank to read the tile from on CGB
+        const BANK     = 0b0000_1000;
+        /// Which color palette to use on CGB
+        const CGB_PALETTE = 0b0000_0111;
+    }
+
+    impl TileAttributes: u8 {
+        /// If true, background and window colors 1-3 are drawn over objects
+        const PRIORITY = 0b1000_0000;
+        /// If tile should be flipped vertically
+        const Y_FLIP   = 0b0100_0000;
+        /// If tile should be flipped horizontally
+        const X_FLIP   = 0b0010_0000;
+        /// Which VRAM bank to read the tile from
+        const BANK     = 0b0000_1000;
+        /// Which color palette to use
+        const PALETTE  = 0b0000_0111;
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct OAMSprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile_index: u8,
+    pub flags: SpriteFlags,
+}
+
+impl OAMSprite {
+    pub fn from(data: [u8; 4]) -> Self {
+        Self {
+            y: data[0],
+            x: data[1],
+            tile_index: data[2],
+            flags: SpriteFlags::from_bits_retain(data[3]),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub struct OAM {
+    #[serde(with = "BigArray")]
+    pub sprites: [OAMSprite; 40],
+}
+
+impl OAM {
+    pub fn new() -> Self {
+        Self {
+            sprites: [OAMSprite::from([0; 4]); 40],
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        let sprite = &self.sprites[usize::from(address / 4)];
+        match address % 4 {
+            0 => sprite.y,
+            1 => sprite.x,
+            2 => sprite.tile_index,
+            3 => sprite.flags.bits(),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        let sprite = &mut self.sprites[usize::from(address / 4)];
+        match address % 4 {
+            0 => sprite.y = value,
+            1 => sprite.x = value,
+            2 => sprite.tile_index = value,
+            3 => sprite.flags = SpriteFlags::from_bits_retain(value),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DMGPalettes {
+    bg: u8,
+    obj0: u8,
+    obj1: u8,
+}
+
+/// Color palette memory of CGB, accessed one byte at a time through an index register
+#[derive(Deserialize, Serialize)]
+pub struct CGBPalettes {
+    /// Eight background palettes of four colors, each color taking two bytes
+    #[serde(with = "BigArray")]
+    bg: [u8; 64],
+    /// Eight object palettes of four colors
+    #[serde(with = "BigArray")]
+    obj: [u8; 64],
+    /// BCPS: background palette index and auto-increment flag
+    bg_index: u8,
+    /// OCPS: object palette index and auto-increment flag
+    obj_index: u8,
+}
+
+impl CGBPalettes {
+    /// Index register bit that increments the index after each data write
+    const AUTO_INCREMENT: u8 = 0b1000_0000;
+
+    fn new() -> Self {
+        Self {
+            bg: [0; 64],
+            obj: [0; 64],
+            bg_index: 0,
+            obj_index: 0,
+        }
+    }
+
+    /// Sets every background color to white, which the boot ROM does
+    pub fn clear_bg(&mut self) {
+        for color in self.bg.chunks_mut(2) {
+            color.copy_from_slice(&WHITE.to_le_bytes());
+        }
+    }
+
+    /// Returns the RGB555 color of given color ID in given palette
+    fn color(data: &[u8; 64], palette: u8, col_id: u8) -> u16 {
+        let i = 8 * usize::from(palette) + 2 * usize::from(col_id);
+        u16::from_le_bytes([data[i], data[i + 1]]) & WHITE
+    }
+
+    /// Writes to the palette data at given index register and increments it if enabled
+    fn write_data(data: &mut [u8; 64], index: &mut u8, value: u8) {
+        data[usize::from(*index & 0x3F)] = value;
+        if *index & Self::AUTO_INCREMENT > 0 {
+            *index = Self::AUTO_INCREMENT | (index.wrapping_add(1) & 0x3F);
+        }
+    }
+}
+
+/// The Game Boy's 160x144 display has 23040 pixels that can
+/// display 4 colors (represented in two bits).
+/// This requires 46080 bits which are represented as 1440 unsigned 32-bit integers.
+pub const DISPLAY_BUFFER_SIZE: usize = (2 * 160 * 144) / 32;
+
+/// Buffer representing the Game Boy's display.
+/// Each pixel takes two bits and the integer's least significant bits represent the leftmost
+/// pixels.
+pub type DisplayBuffer = [u32; DISPLAY_BUFFER_SIZE];
+
+fn empty_display() -> DoubleBuffer<DisplayBuffer> {
+    DoubleBuffer::new([0; DISPLAY_BUFFER_SIZE], [0; DISPLAY_BUFFER_SIZE])
+}
+
+/// In CGB mode each of the 23040 pixels has a 15-bit color
+pub const COLOR_DISPLAY_BUFFER_SIZE: usize = 160 * 144;
+
+/// Buffer representing the Game Boy Color's display, row by row.
+/// Each pixel is an RGB555 color, where the least significant bits are red
+pub type ColorDisplayBuffer = [u16; COLOR_DISPLAY_BUFFER_SIZE];
+
+/// RGB555 white, which the display shows while the PPU is disabled
+const WHITE: u16 = 0x7FFF;
+
+fn empty_color_display() -> DoubleBuffer<ColorDisplayBuffer> {
+    DoubleBuffer::new(
+        [WHITE; COLOR_DISPLAY_BUFFER_SIZE],
+        [WHITE; COLOR_DISPLAY_BUFFER_SIZE],
+    )
+}
+
+/// Describes the current drawing state of the PPU
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum PPUMode {
+    HBlank = 0,
+    VBlank = 1,
+    OAMScan = 2,
+    Drawing = 3,
+}
+
+impl From<PPUMode> for u8 {
+    fn from(src: PPUMode) -> u8 {
+        use PPUMode::*;
+        match src {
+            HBlank => 0,
+            VBlank => 1,
+            OAMScan => 2,
+            Drawing => 3,
+        }
+    }
+}
+
+/// Describes the active state of the PPU
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum PPUState {
+    /// Active and drawing normally
+    Active,
+    /// Disabled, no registers are updated, display shows white
+    Disabled,
+    /// Set active this frame, registers are updated, display shows white until next frame
+    Starting,
+}
+
+/// The graphics processing unit
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub struct PPU {
+    pub state: PPUState,
+    #[serde(skip)]
+    #[serde(default = "empty_display")]
+    pub display: DoubleBuffer<DisplayBuffer>,
+    /// Display drawn to instead of the 2-bit one in CGB mode
+    #[serde(skip)]
+    #[serde(default = "empty_color_display")]
+    pub color_display: DoubleBuffer<ColorDisplayBuffer>,
+    /// If running in CGB mode, with color palettes, background attributes and VRAM banks
+    pub cgb: bool,
+    /// If emulating Game Boy Color hardware, which lacks some of the DMG quirks
+    /// even when running a DMG game
+    pub cgb_hardware: bool,
+    /// Two 8 KiB VRAM banks, of which DMG only uses the first
+    #[serde(with = "BigArray")]
+    pub vram: [u8; 0x4000],
+    /// VRAM bank the CPU accesses (VBK)
+    vram_bank: u8,
+    pub oam: OAM,
+    /// The source address for OAM DMA tranfer
+    pub oam_dma_source: u8,
+    /// If true, an OAM DMA transfer is requested.
+    /// This is set back to false when transfer begins on CPU
+    pub oam_dma_request: bool,
+    /// If an OAM DMA transfer is ongoing, during which OAM can't be accessed
+    pub oam_dma_active: bool,
+    /// LCD control register
+    pub lcdc: LCDControl,
+    /// The current horizontal scan position
+    pub lx: u16,
+    /// The current scanline
+    pub ly: u8,
+    /// If on line 153, after LY has already changed to 0
+    last_line: bool,
+    /// Background scroll position X
+    pub bg_x: u8,
+    /// Background scroll position Y
+    pub bg_y: u8,
+    /// Window position X, where the window starts at WX - 7
+    pub win_x: u8,
+    /// Window position Y
+    pub win_y: u8,
+    /// Window line counter
+    pub win_line: u8,
+    /// The current palettes used for rendering
+    pub palettes: DMGPalettes,
+    /// Color palettes used for rendering in CGB mode
+    pub color_palettes: CGBPalettes,
+    /// If objects are prioritized by X coordinate like on DMG instead of by OAM index (OPRI)
+    dmg_object_priority: bool,
+    /// Currently requested interrupt.
+    /// Is reset to 0 after handling on CPU
+    pub interrupt_request: InterruptFlag,
+    pub stat_enable: STATEnable,
+    /// State of the STAT interrupt line, which requests the interrupt on its rising edge
+    stat_line: bool,
+    /// If a write to STAT triggered an interrupt that's requested on the next cycle
+    stat_write_interrupt: bool,
+    /// Current drawing state
+    pub mode: PPUMode,
+    /// Set when HBlank starts, which runs HBlank DMA.
+    /// This is set back to false when handled on CPU
+    pub hblank_started: bool,
+    /// Register to compare to scanline coordinate for interrupts
+    pub lyc: u8,
+    /// Background or window pixels waiting to be shifted out
+    bg_fifo: VecDeque<BackgroundPixel>,
+    /// Object pixels mixed with the background pixels as they're shifted out
+    obj_fifo: VecDeque<ObjectPixel>,
+    fetcher: Fetcher,
+    /// Object being fetched, if any
+    object_fetch: Option<ObjectFetch>,
+    /// Objects on the current scanline that haven't been fetched yet
+    line_sprites: Vec<LineSprite>,
+    /// The next pixel to be drawn on the current scanline
+    lcd_x: u8,
+    /// Amount of pixels to shift out without drawing them
+    discard: u8,
+    /// Dots left before the fetcher starts on the current scanline
+    startup_dots: u8,
+    /// If WY has matched the scanline this frame, allowing the window to be drawn
+    window_y_reached: bool,
+    /// If the window has been drawn on the current scanline
+    window_on_line: bool,
+    /// Amount of VRAM and OAM accesses dropped because the PPU was using the memory
+    #[serde(skip)]
+    blocked_accesses: Cell<u32>,
+}
+
+impl PPU {
+    /// Dots on line 153 after which LY reads 0
+    const LAST_LINE_LY_DOTS: u16 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            state: PPUState::Active,
+            display: empty_display(),
+            color_display: empty_color_display(),
+            cgb: false,
+            cgb_hardware: false,
+            vram: [0; 0x4000],
+            vram_bank: 0,
+            oam: OAM::new(),
+            oam_dma_source: 0,
+            oam_dma_request: false,
+            oam_dma_active: false,
+            lcdc: LCDControl::from_bits_truncate(0b0000_0000),
+            lx: 0,
+            ly: 0,
+            last_line: false,
+            bg_x: 0,
+            bg_y: 0,
+            win_x: 0,
+            win_y: 0,
+            win_line: 0,
+            palettes: DMGPalettes {
+                bg: 0,
+                obj0: 0,
+                obj1: 0,
+            },
+            color_palettes: CGBPalettes::new(),
+            dmg_object_priority: false,
+            interrupt_request: InterruptFlag::from_bits_truncate(0),
+            stat_enable: STATEnable::from_bits_truncate(0),
+            stat_line: false,
+            stat_write_interrupt: false,
+            mode: PPUMode::OAMScan,
+            hblank_started: false,
+            lyc: 0,
+            bg_fifo: VecDeque::with_capacity(16),
+            obj_fifo: VecDeque::with_capacity(8),
+            fetcher: Fetcher::new(false),
+            object_fetch: None,
+            line_sprites: Vec::with_capacity(10),
+            lcd_x: 0,
+            discard: 0,
+            startup_dots: 0,
+            window_y_reached: false,
+            window_on_line: false,
+            blocked_accesses: Cell::new(0),
+        }
+    }
+
+    /// Returns the amount of VRAM and OAM accesses dropped so far because the PPU was using the memory
+    pub fn blocked_accesses(&self) -> u32 {
+        self.blocked_accesses.get()
+    }
+
+    /// If the CPU can't access VRAM, which is read by the PPU while drawing
+    fn vram_blocked(&self) -> bool {
+        self.mode == PPUMode::Drawing
+    }
+
+    /// If the CPU can't access OAM, which is read by the PPU while scanning objects and drawing,
+    /// and written to by OAM DMA
+    fn oam_blocked(&self) -> bool {
+        matches!(self.mode, PPUMode::OAMScan | PPUMode::Drawing) || self.oam_dma_active
+    }
+
+    /// Records an access that was dropped because the memory was blocked
+    fn block_access(&self, address: u16, write: bool) {
+        self.blocked_accesses
+            .set(self.blocked_accesses.get().wrapping_add(1));
+        let access = if write { "write to" } else { "read from" };
+        log::debug!(
+            "Blocked {access} {address:#06X} during PPU mode {} (LY {}, dot {})",
+            u8::from(self.mode),
+            self.ly,
+            self.lx
+        );
+    }
+
+    pub fn cycle(&mut self) {
+        use {PPUMode::*, PPUState::*};
+        self.interrupt_request = InterruptFlag::from_bits_truncate(0);
+        // Interrupt triggered by a STAT write in between cycles
+        if self.stat_write_interrupt {
+            self.stat_write_interrupt = false;
+            self.interrupt_request.insert(InterruptFlag::LCD);
+        }
+
+        if self.state == Disabled {
+            return;
+        }
+
+        self.lx += 1;
+        if self.lx == 456 {
+            self.lx = 0;
+            // LY already reads 0 for most of line 153
+            self.ly = if self.last_line { 0 } else { self.ly + 1 };
+            self.last_line = false;
+
+            match self.ly {
+                0..=143 => {
+                    if self.ly == 0 {
+                        // Start drawing new frame
+                        self.win_line = 0;
+                        self.window_y_reached = false;
+                        // End drawing delay after PPU was enabled again
+                        if self.state == Starting {
+                            self.state = Active;
+                        }
+                    }
+                    self.mode = OAMScan;
+                }
+                144 => {
+                    // Send VBlank interrupt
+                    self.interrupt_request.insert(InterruptFlag::VBLANK);
+                    self.mode = VBlank;
+                    // Swap double buffer for rendering new frame
+                    self.display.swap();
+                    self.color_display.swap();
+                }
+                _ => {}
+            }
+        } else if self.ly == 153 && self.lx >= Self::LAST_LINE_LY_DOTS {
+            self.ly = 0;
+            self.last_line = true;
+        }
+
+        match self.mode {
+            OAMScan if self.lx == 80 => {
+                self.start_drawing();
+                self.mode = Drawing;
+            }
+            Drawing => {
+                // Mode 3 lasts until the last pixel of the scanline has been shifted out,
+                // which takes longer with scrolling, window and objects
+                if self.lcd_x == 160 {
+                    self.finish_drawing();
+                    self.mode = HBlank;
+                    self.hblank_started = true;
+                } else {
+                    self.draw_dot();
+                }
+            }
+            _ => {}
+        }
+
+        // All STAT sources share one interrupt line,
+        // so the interrupt is only requested when none of them were active before
+        let stat_line = self.stat_line(self.stat_enable);
+        if stat_line && !self.stat_line {
+            self.interrupt_request.insert(InterruptFlag::LCD);
+        }
+        self.stat_line = stat_line;
+    }
+
+    /// Returns the state of the STAT interrupt line with given sources enabled
+    fn stat_line(&self, enable: STATEnable) -> bool {
+        use PPUMode::*;
+        let mode_source = match self.mode {
+            HBlank => STATEnable::Mode0,
+            VBlank => STATEnable::Mode1,
+            OAMScan => STATEnable::Mode2,
+            Drawing => STATEnable::empty(),
+        };
+        // Mode 2 source is also active as VBlank starts
+        let vblank_start = self.mode == VBlank
+            && self.ly == 144
+            && self.lx == 0
+            && enable.intersects(STATEnable::Mode2);
+        (self.lyc == self.ly && enable.intersects(STATEnable::LYC))
+            || enable.intersects(mode_source)
+            || vblank_start
+    }
+
+    /// Get color value from given palette
+    fn get_palette_color(&mut self, col_id: u8, palette: u8) -> u8 {
+        (palette >> (2 * col_id)) & 0b11
+    }
+
+    /// Saves given RGB555 color into the color display buffer
+    fn set_color_pixel(&mut self, x: u8, y: u8, color: u16) {
+        if self.state == PPUState::Starting {
+            return;
+        }
+        self.color_display[(y as usize) * 160 + (x as usize)] = color;
+    }
+
+    /// Saves given color into the display buffer
+    fn set_pixel(&mut self, x: u8, y: u8, col: u8) {
+        // When PPU has been re-enabled this frame,
+        // the display stays white until next frame
+        if self.state == PPUState::Starting {
+            return;
+        }
+        let i = (((y as usize) * 2 * 160) + ((x as usize) * 2)) / 32;
+        let shift = (x % 16) * 2;
+        self.display[i] &= !(0b11 << shift);
+        self.display[i] |= (col as u32) << shift;
+    }
+
+    /// Clears the display to white
+    pub fn blank_display(&mut self) {
+        self.display.fill(0);
+        self.display.swap();
+        self.display.fill(0);
+        self.color_display.fill(WHITE);
+        self.color_display.swap();
+        self.color_display.fill(WHITE);
+    }
+
+    /// Returns index in VRAM for given address in the bank selected by the CPU
+    fn vram_index(&self, address: u16) -> usize {
+        0x2000 * usize::from(self.vram_bank) + usize::from(address - 0x8000)
+    }
+
+    /// Reads VRAM from the bank selected by the CPU, regardless of the PPU using it
+    pub fn read_vram(&self, address: u16) -> u8 {
+        self.vram[self.vram_index(address)]
+    }
+
+    /// Writes VRAM to the bank selected by the CPU, regardless of the PPU using it
+    pub fn write_vram(&mut self, address: u16, value: u8) {
+        self.vram[self.vram_index(address)] = value;
+    }
+
+    fn disable(&mut self) {
+        if self.state == PPUState::Disabled {
+            return;
+        }
+        // Clear the display only if it has been drawn to since last disable
+        if self.state == PPUState::Active {
+            self.blank_display();
+        }
+        self.state = PPUState::Disabled;
+        // Clear registers
+        self.mode = PPUMode::HBlank;
+        self.lx = 0;
+        self.ly = 0;
+        self.last_line = false;
+        self.stat_line = false;
+        self.lcd_x = 0;
+        self.window_y_reached = false;
+    }
+}
+
+impl MemoryAccess for PPU {
+    fn mem_read(&self, address: u16) -> u8 {
+        match address {
+            // VRAM and OAM read $FF while the PPU is using them
+            0x8000..=0x9FFF if self.vram_blocked() => {
+                self.block_access(address, false);
+                0xFF
+            }
+            0xFE00..=0xFE9F if self.oam_blocked() => {
+                self.block_access(address, false);
+                0xFF
+            }
+            // Color palette data can't be accessed while drawing either
+            0xFF69 | 0xFF6B if self.vram_blocked() => {
+                self.block_access(address, false);
+                0xFF
+            }
+            // VRAM
+            0x8000..=0x9FFF => self.read_vram(address),
+            // OAM
+            0xFE00..=0xFE9F => self.oam.read(address - 0xFE00),
+            // LCDC
+            0xFF40 => self.lcdc.bits(),
+            // STAT
+            0xFF41 => {
+                let lyc = ((self.lyc == self.ly) as u8) << 2;
+                // Unused bit 7 is read as 1
+                0b1000_0000 | self.stat_enable.bits() | lyc | u8::from(self.mode)
+            }
+            // SCY
+            0xFF42 => self.bg_y,
+            // SCX
+            0xFF43 => self.bg_x,
+            // LY
+            0xFF44 => self.ly,
+            // LYC
+            0xFF45 => self.lyc,
+            // OAM DMA
+            0xFF46 => self.oam_dma_source,
+            // BGP
+            0xFF47 => self.palettes.bg,
+            // OBP0
+            0xFF48 => self.palettes.obj0,
+            // OBP1
+            0xFF49 => self.palettes.obj1,
+            // WY
+            0xFF4A => self.win_y,
+            // WX
+            0xFF4B => self.win_x,
+            // VBK
+            0xFF4F => 0b1111_1110 | self.vram_bank,
+            // BCPS, BCPD
+            0xFF68 => 0b0100_0000 | self.color_palettes.bg_index,
+            0xFF69 => self.color_palettes.bg[usize::from(self.color_palettes.bg_index & 0x3F)],
+            // OCPS, OCPD
+            0xFF6A => 0b0100_0000 | self.color_palettes.obj_index,
+            0xFF6B => self.color_palettes.obj[usize::from(self.color_palettes.obj_index & 0x3F)],
+            // OPRI
+            0xFF6C => 0b1111_1110 | self.dmg_object_priority as u8,
+            _ => 0xFF,
+        }
+    }
+    fn mem_write(&mut self, address: u16, value: u8) {
+        match address {
+            // Writes to VRAM and OAM are dropped while the PPU is using them
+            0x8000..=0x9FFF if self.vram_blocked() => self.block_access(address, true),
+            0xFE00..=0xFE9F if self.oam_blocked() => self.block_access(address, true),
+            0xFF69 | 0xFF6B if self.vram_blocked() => self.block_access(address, true),
+            // VRAM
+            0x8000..=0x9FFF => self.write_vram(address, value),
+            // OAM
+            0xFE00..=0xFE9F => self.oam.write(address - 0xFE00, value),
+            // LCDC
+            0xFF40 => {
+                self.lcdc = LCDControl::from_bits_truncate(value);
+                let enabled = self.lcdc.intersects(LCDControl::ENABLE);
+                if self.state != PPUState::Disabled && !enabled {
+                    self.disable();
+                } else if self.state == PPUState::Disabled && enabled {
+                    self.state = PPUState::Starting;
+                    self.mode = PPUMode::OAMScan;
+                }
+            }
+            // STAT
+            0xFF41 => {
+                // On DMG, writing to STAT enables all sources but mode 2 for one cycle,
+                // which causes an interrupt during HBlank, VBlank or LY=LYC
+                if self.state != PPUState::Disabled && !self.cgb_hardware {
+                    let glitch_sources = STATEnable::LYC | STATEnable::Mode1 | STATEnable::Mode0;
+                    let stat_line = self.stat_line(glitch_sources);
+                    if stat_line && !self.stat_line {
+                        self.stat_write_interrupt = true;
+                    }
+                    self.stat_line = stat_line;
+                }
+                self.stat_enable = STATEnable::from_bits_truncate(value);
+            }
+            // SCY
+            0xFF42 => self.bg_y = value,
+            // SCX
+            0xFF43 => self.bg_x = value,
+            // LYC
+            0xFF45 => self.lyc = value,
+            // OAM DMA
+            0xFF46 => {
+                self.oam_dma_source = value;
+                self.oam_dma_request = true;
+            }
+            // BGP
+            0xFF47 => self.palettes.bg = value,
+            // OBP0
+            0xFF48 => self.palettes.obj0 = value,
+            // OBP1
+            0xFF49 => self.palettes.obj1 = value,
+            // WY
+            0xFF4A => self.win_y = value,
+            // WX
+            0xFF4B => self.win_x = value,
+            // VBK
+            0xFF4F => self.vram_bank = value & 1,
+            // BCPS, BCPD
+            0xFF68 => self.color_palettes.bg_index = value & 0b1011_1111,
+            0xFF69 => {
+                let palettes = &mut self.color_palettes;
+                CGBPalettes::write_data(&mut palettes.bg, &mut palettes.bg_index, value);
+            }
+            // OCPS, OCPD
+            0xFF6A => self.color_palettes.obj_index = value & 0b1011_1111,
+            0xFF6B => {
+                let palettes = &mut self.color_palettes;
+                CGBPalettes::write_data(&mut palettes.obj, &mut palettes.obj_index, value);
+            }
+            // OPRI
+            0xFF6C => self.dmg_object_priority = value & 1 > 0,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use super::*;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// Width of printed images in pixels
+pub const PRINT_WIDTH: usize = 160;
+
+/// Image printed by the Game Boy Printer
+#[derive(Debug, Clone)]
+pub struct PrintedImage {
+    /// Height of the image in pixels
+    pub height: usize,
+    /// Shade of each pixel from 0 (white) to 3 (black), stored row by row
+    pub pixels: Vec<u8>,
+    /// Print darkness from $00 (lightest) to $7F (darkest), where $40 is the default
+    pub exposure: u8,
+}
+
+/// Finished prints and the paper still being printed
+#[derive(Default)]
+struct Prints {
+    finished: VecDeque<PrintedImage>,
+    /// Pixels of the paper being printed, which is finished once the paper is fed forward
+    paper: Vec<u8>,
+    /// Exposure of the last print on the paper
+    exposure: u8,
+}
+
+impl Prints {
+    /// Moves the printed paper to the finished prints
+    fn finish_paper(&mut self) {
+        if self.paper.is_empty() {
+            return;
+        }
+        let pixels = std::mem::take(&mut self.paper);
+        self.finished.push_back(PrintedImage {
+            height: pixels.len() / PRINT_WIDTH,
+            pixels,
+            exposure: self.exposure,
+        });
+    }
+}
+
+/// Shared queue of finished prints.
+/// Clones share the same queue, so a clone can be kept by the host to collect the prints
+#[derive(Clone, Default)]
+pub struct PrintQueue {
+    prints: Rc<RefCell<Prints>>,
+}
+
+impl PrintQueue {
+    /// Removes and returns all finished prints
+    pub fn take(&self) -> Vec<PrintedImage> {
+        self.prints.borrow_mut().finished.drain(..).collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PrinterStatus(u8);
+
+bitflags! {
+    impl PrinterStatus: u8 {
+        const LOW_BATTERY   = 0b1000_0000;
+        const OTHER_ERROR   = 0b0100_0000;
+        const PAPER_JAM     = 0b0010_0000;
+        const PACKET_ERROR  = 0b0001_0000;
+        const UNPROCESSED   = 0b0000_1000;
+        const IMAGE_FULL    = 0b0000_0100;
+        const BUSY          = 0b0000_0010;
+        const CHECKSUM      = 0b0000_0001;
+    }
+}
+
+/// Part of the packet that the next byte belongs to
+#[derive(Clone, Copy, PartialEq)]
+enum PacketState {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+/// Simulates the Game Boy Printer connected to the serial port
+pub struct Printer {
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    /// Sum of the packet bytes from command to data
+    checksum: u16,
+    /// Checksum sent at the end of the packet
+    received_checksum: u16,
+    status: PrinterStatus,
+    /// Amount of status requests that still report the printer busy
+    busy_polls: u8,
+    /// Tile data received with data packets
+    buffer: Vec<u8>,
+    /// Serial clock ticks since the last received byte
+    idle_ticks: u16,
+    queue: PrintQueue,
+}
+
+impl Printer {
+    /// Size of the printer memory, enough for 9 data packets
+    const BUFFER_SIZE: usize = 0x2000;
+    /// Amount of status requests the printer is busy for after starting to print
+    const PRINT_POLLS: u8 = 4;
+    /// Serial clock ticks without any packets after which the paper is finished, about a second.
+    /// Prints without margins are continued by the next print, so they are only finished
+    /// once the game stops printing
+    const FINISH_TICKS: u16 = 8192;
+
+    pub fn new() -> Self {
+        Self {
+            state: PacketState::Magic1,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            status: PrinterStatus::empty(),
+            busy_polls: 0,
+            buffer: Vec::new(),
+            idle_ticks: 0,
+            queue: PrintQueue::default(),
+        }
+    }
+
+    /// Returns the queue the finished prints are pushed to
+    pub fn queue(&self) -> PrintQueue {
+        self.queue.clone()
+    }
+
+    /// Handles a fully received packet
+    fn run_command(&mut self) {
+        if self.checksum != self.received_checksum {
+            self.status.insert(PrinterStatus::CHECKSUM);
+            return;
+        }
+        self.status.remove(PrinterStatus::CHECKSUM);
+        match self.command {
+            // INIT
+            0x01 => {
+                self.buffer.clear();
+                self.status = PrinterStatus::empty();
+                self.busy_polls = 0;
+            }
+            // PRINT
+            0x02 => {
+                if self.data.len() < 4 {
+                    self.status.insert(PrinterStatus::PACKET_ERROR);
+                    return;
+                }
+                self.print(self.data[1], self.data[2], self.data[3]);
+                self.buffer.clear();
+                self.status = PrinterStatus::BUSY;
+                self.busy_polls = Self::PRINT_POLLS;
+            }
+            // DATA
+            0x04 => {
+                // An empty data packet marks the end of the image data
+                if self.data.is_empty() {
+                    self.status.insert(PrinterStatus::IMAGE_FULL);
+                    return;
+                }
+                let data = if self.compressed {
+                    Self::decompress(&self.data)
+                } else {
+                    self.data.clone()
+                };
+                let space = Self::BUFFER_SIZE - self.buffer.len();
+                self.buffer
+                    .extend_from_slice(&data[..data.len().min(space)]);
+                self.status.insert(PrinterStatus::UNPROCESSED);
+            }
+            // BREAK
+            0x08 => {
+                self.buffer.clear();
+                self.status = PrinterStatus::empty();
+                self.busy_polls = 0;
+            }
+            // STATUS
+            0x0F => {
+                if self.busy_polls > 0 {
+                    self.busy_polls -= 1;
+                    if self.busy_polls == 0 {
+                        self.status.remove(PrinterStatus::BUSY);
+                    }
+                }
+            }
+            _ => {
+                log::warn!("Unknown printer command {:#04X}", self.command);
+                self.status.insert(PrinterStatus::PACKET_ERROR);
+            }
+        }
+    }
+
+    /// Decompresses run-length encoded data packet
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let control = data[i];
+            i += 1;
+            if control & 0x80 > 0 {
+                // Single byte repeated
+                let Some(&byte) = data.get(i) else {
+                    break;
+                };
+                output.extend(std::iter::repeat_n(byte, (control & 0x7F) as usize + 2));
+                i += 1;
+            } else {
+                // Uncompressed bytes
+                let end = (i + control as usize + 1).min(data.len());
+                output.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+        }
+        output
+    }
+
+    /// Prints the buffered tile data on paper using given print settings
+    fn print(&mut self, margins: u8, palette: u8, exposure: u8) {
+        let mut prints = self.queue.prints.borrow_mut();
+        // Feeding paper before printing finishes the previous print
+        let margin_before = margins >> 4;
+        let margin_after = margins & 0x0F;
+        if margin_before > 0 {
+            prints.finish_paper();
+        }
+        prints.exposure = exposure & 0x7F;
+
+        // Palette $00 is treated the same as the default $E4
+        let palette = if palette == 0 { 0xE4 } else { palette };
+        // Tile data is arranged as rows of 20 tiles
+        let tile_rows = self.buffer.len() / 16 / 20;
+        for tile_row in 0..tile_rows {
+            for line in 0..8 {
+                for x in 0..PRINT_WIDTH {
+                    let tile = tile_row * 20 + x / 8;
+                    let address = tile * 16 + line * 2;
+                    let bit = 7 - (x % 8);
+                    let low = (self.buffer[address] >> bit) & 1;
+                    let high = (self.buffer[address + 1] >> bit) & 1;
+                    let color = (high << 1) | low;
+                    prints.paper.push((palette >> (color * 2)) & 0b11);
+                }
+            }
+        }
+
+        if margin_after > 0 {
+            prints.finish_paper();
+        }
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialDevice for Printer {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        use PacketState::*;
+        self.idle_ticks = 0;
+        let mut response = 0x00;
+        self.state = match self.state {
+            // Packets start with two magic bytes
+            Magic1 if byte == 0x88 => Magic2,
+            Magic1 => Magic1,
+            Magic2 if byte == 0x33 => Command,
+            Magic2 => Magic1,
+            Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                Compression
+            }
+            Compression => {
+                self.compressed = byte & 1 > 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                LengthLow
+            }
+            LengthLow => {
+                self.length = byte as u16;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                LengthHigh
+            }
+            LengthHigh => {
+                self.length |= (byte as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.clear();
+                if self.length > 0 { Data } else { ChecksumLow }
+            }
+            Data => {
+                self.data.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.data.len() < self.length as usize {
+                    Data
+                } else {
+                    ChecksumLow
+                }
+            }
+            ChecksumLow => {
+                self.received_checksum = byte as u16;
+                ChecksumHigh
+            }
+            ChecksumHigh => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.run_command();
+                Alive
+            }
+            // The printer identifies itself while receiving the first of two trailing bytes
+            Alive => {
+                response = 0x81;
+                Status
+            }
+            Status => {
+                response = self.status.bits();
+                Magic1
+            }
+        };
+        response
+    }
+
+    fn idle(&mut self) {
+        if self.idle_ticks < Self::FINISH_TICKS {
+            self.idle_ticks += 1;
+            if self.idle_ticks == Self::FINISH_TICKS {
+                self.queue.prints.borrow_mut().finish_paper();
+            }
+        }
+    }
+}
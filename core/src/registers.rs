@@ -49,19 +49,19 @@ pub struct Registers {
 }
 
 impl Registers {
-    pub fn new() -> Self {
+    /// State of registers when the system is powered on, before running the boot ROM
+    pub fn power_on() -> Self {
         Self {
-            // State of registers after executing boot ROM
-            a: 0x01,
-            f: FlagReg::from_bits_truncate(0xB0),
+            a: 0,
+            f: FlagReg::from_bits_truncate(0),
             b: 0,
-            c: 0x13,
-            d: 0x00,
-            e: 0xD8,
-            h: 0x01,
-            l: 0x4D,
-            sp: 0xFFFE,
-            pc: 0x0100,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
         }
     }
 
@@ -0,0 +1,187 @@
+use super::*;
+use std::{cell::RefCell, rc::Rc};
+
+/// Device connected to the serial port through the link cable.
+/// Can be implemented by the host to connect the Game Boy to another device
+pub trait SerialDevice {
+    /// Called when the Game Boy starts a transfer using its internal clock.
+    /// Receives the byte sent by the Game Boy and returns the byte sent back by the device
+    fn transfer(&mut self, byte: u8) -> u8;
+
+    /// Called periodically while the Game Boy waits for the device to clock a transfer.
+    /// Receives the byte the Game Boy is ready to send,
+    /// and returns the byte sent by the device if it has started a transfer
+    fn external_transfer(&mut self, _byte: u8) -> Option<u8> {
+        None
+    }
+
+    /// Called periodically while the Game Boy isn't transferring anything,
+    /// so the device can handle transfers started by the other side that the Game Boy isn't ready for
+    fn idle(&mut self) {}
+}
+
+/// Serial device representing an unconnected link cable.
+/// Every received bit is 1, and external clock transfers never finish
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn transfer(&mut self, _byte: u8) -> u8 {
+        0xFF
+    }
+}
+
+pub(crate) fn disconnected() -> Box<dyn SerialDevice> {
+    Box::new(Disconnected)
+}
+
+/// Serial device that records every byte sent by the Game Boy,
+/// acting otherwise like an unconnected link cable.
+/// Clones of the logger share the same recorded bytes,
+/// so a clone can be kept by the host to read them
+#[derive(Clone, Default)]
+pub struct SerialLogger {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SerialLogger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the bytes sent so far
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.borrow().clone()
+    }
+
+    /// Returns the bytes sent so far as text, which is how test ROMs report their results
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
+    }
+
+    /// Clears the recorded bytes
+    pub fn clear(&self) {
+        self.bytes.borrow_mut().clear();
+    }
+}
+
+impl SerialDevice for SerialLogger {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.bytes.borrow_mut().push(byte);
+        0xFF
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct SerialControl(u8);
+
+bitflags! {
+    impl SerialControl: u8 {
+        const TRANSFER_ENABLE = 0b1000_0000;
+        const CLOCK_SELECT    = 0b0000_0001;
+    }
+}
+
+/// Simulates the serial port registers (SB and SC)
+#[derive(Deserialize, Serialize)]
+pub struct Serial {
+    /// Serial transfer data, shifted out from the top and in from the bottom
+    pub data: u8,
+    pub control: SerialControl,
+    /// Byte being shifted in during an internal clock transfer
+    incoming: u8,
+    /// Bits left in the ongoing transfer
+    bits_left: u8,
+    pub request_interrupt: bool,
+    /// State of the divider bit that clocks transfers on the previous T-cycle
+    previous_clock: bool,
+    #[serde(skip, default = "disconnected")]
+    device: Box<dyn SerialDevice>,
+}
+
+impl Serial {
+    /// Divider bit whose falling edge shifts one bit, giving the 8192 Hz internal clock
+    const CLOCK_BIT: u16 = 0b1_0000_0000;
+
+    pub fn new() -> Self {
+        Self {
+            data: 0,
+            control: SerialControl::from_bits_truncate(0),
+            incoming: 0xFF,
+            bits_left: 0,
+            request_interrupt: false,
+            previous_clock: false,
+            device: disconnected(),
+        }
+    }
+
+    /// Connects given device to the serial port
+    pub fn set_device(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = device;
+    }
+
+    /// Cycles the serial port forward by one T-cycle based on the timer divider
+    pub fn cycle(&mut self, div: u16) {
+        self.request_interrupt = false;
+
+        let clock = div & Self::CLOCK_BIT > 0;
+        let falling_edge = self.previous_clock && !clock;
+        self.previous_clock = clock;
+        if !falling_edge {
+            return;
+        }
+        if !self.control.contains(SerialControl::TRANSFER_ENABLE) {
+            self.device.idle();
+            return;
+        }
+
+        if !self.control.contains(SerialControl::CLOCK_SELECT) {
+            // The other device clocks the transfer, which is finished all at once
+            if let Some(byte) = self.device.external_transfer(self.data) {
+                self.data = byte;
+                self.finish_transfer();
+            }
+            return;
+        }
+
+        // The whole byte is exchanged with the device when the transfer starts,
+        // and shifted in one bit at a time
+        if self.bits_left == 0 {
+            self.incoming = self.device.transfer(self.data);
+            self.bits_left = 8;
+        }
+        self.bits_left -= 1;
+        self.data = (self.data << 1) | ((self.incoming >> self.bits_left) & 1);
+        if self.bits_left == 0 {
+            self.finish_transfer();
+        }
+    }
+
+    fn finish_transfer(&mut self) {
+        self.control.remove(SerialControl::TRANSFER_ENABLE);
+        self.bits_left = 0;
+        self.request_interrupt = true;
+    }
+}
+
+impl MemoryAccess for Serial {
+    fn mem_read(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.data,
+            // Unused bits are read as 1
+            0xFF02 => self.control.bits() | 0b0111_1110,
+            _ => unreachable!(),
+        }
+    }
+
+    fn mem_write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF01 => self.data = value,
+            0xFF02 => {
+                self.control = SerialControl::from_bits_truncate(value);
+                // Writing to the control register restarts the transfer
+                self.bits_left = 0;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
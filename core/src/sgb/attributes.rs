@@ -0,0 +1,123 @@
+use super::*;
+
+impl SGB {
+    /// Sets the palette of the tile at given position on the Game Boy screen
+    fn set_attribute(&mut self, x: usize, y: usize, palette: u8) {
+        if x < 20 && y < 18 {
+            self.attributes[y * 20 + x] = palette & 0b11;
+        }
+    }
+
+    /// ATTR_BLK: sets the palettes inside, on the border of and outside rectangles
+    pub(super) fn attribute_blocks(&mut self, data: &[u8]) {
+        let count = usize::from(data[1] & 0x1F);
+        for block in data[2..].chunks_exact(6).take(count) {
+            let control = block[0] & 0b111;
+            let palettes = block[1];
+            let (inside, outside) = (palettes & 0b11, (palettes >> 4) & 0b11);
+            // With only the inside or outside selected, the border takes its palette
+            let (paint_border, border) = match control {
+                0b001 => (true, inside),
+                0b100 => (true, outside),
+                _ => (control & 0b010 > 0, (palettes >> 2) & 0b11),
+            };
+            let (x1, y1, x2, y2) = (
+                usize::from(block[2] & 0x1F),
+                usize::from(block[3] & 0x1F),
+                usize::from(block[4] & 0x1F),
+                usize::from(block[5] & 0x1F),
+            );
+            for y in 0..18 {
+                for x in 0..20 {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let on_border = within && (x == x1 || x == x2 || y == y1 || y == y2);
+                    let palette = if on_border {
+                        paint_border.then_some(border)
+                    } else if within {
+                        (control & 0b001 > 0).then_some(inside)
+                    } else {
+                        (control & 0b100 > 0).then_some(outside)
+                    };
+                    if let Some(palette) = palette {
+                        self.set_attribute(x, y, palette);
+                    }
+                }
+            }
+        }
+    }
+
+    /// ATTR_LIN: sets the palettes of whole rows or columns
+    pub(super) fn attribute_lines(&mut self, data: &[u8]) {
+        let count = usize::from(data[1]);
+        for &line in data[2..].iter().take(count) {
+            let index = usize::from(line & 0x1F);
+            let palette = (line >> 5) & 0b11;
+            if line & 0x80 > 0 {
+                (0..20).for_each(|x| self.set_attribute(x, index, palette));
+            } else {
+                (0..18).for_each(|y| self.set_attribute(index, y, palette));
+            }
+        }
+    }
+
+    /// ATTR_DIV: divides the screen in two with a line, setting the palettes of both halves
+    /// and the line
+    pub(super) fn attribute_divide(&mut self, data: &[u8]) {
+        let after = data[1] & 0b11;
+        let before = (data[1] >> 2) & 0b11;
+        let on_line = (data[1] >> 4) & 0b11;
+        let horizontal = data[1] & 0x40 > 0;
+        let line = usize::from(data[2] & 0x1F);
+        for y in 0..18 {
+            for x in 0..20 {
+                let position = if horizontal { y } else { x };
+                let palette = match position.cmp(&line) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+                self.set_attribute(x, y, palette);
+            }
+        }
+    }
+
+    /// ATTR_CHR: sets the palettes of tiles one by one, starting from given tile
+    pub(super) fn attribute_characters(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (usize::from(data[1]), usize::from(data[2]));
+        let count = usize::from(u16::from_le_bytes([data[3], data[4]]));
+        let vertical = data[5] & 1 > 0;
+        let palettes = data[6..]
+            .iter()
+            .flat_map(|byte| (0..4).rev().map(move |i| (byte >> (2 * i)) & 0b11));
+        for palette in palettes.take(count) {
+            self.set_attribute(x, y, palette);
+            // Writing wraps to the next row or column at the edge of the screen
+            if vertical {
+                y += 1;
+                if y == 18 {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == 20 {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// Sets the palettes of all tiles from an attribute file sent with ATTR_TRN
+    pub(super) fn apply_attribute_file(&mut self, index: u8) {
+        let index = usize::from(index);
+        if index >= 45 {
+            return;
+        }
+        // Each byte holds the palettes of four tiles, the first one in the top bits
+        let file = &self.attribute_files[index * 90..(index + 1) * 90];
+        for (i, attribute) in self.attributes.iter_mut().enumerate() {
+            *attribute = (file[i / 4] >> (2 * (3 - i % 4))) & 0b11;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::*;
+
+/// Width of the Super Game Boy picture, including the border
+pub const SGB_FRAME_WIDTH: usize = 256;
+/// Height of the Super Game Boy picture, including the border
+pub const SGB_FRAME_HEIGHT: usize = 224;
+
+/// Super Game Boy picture with the colored Game Boy screen inside the border, row by row.
+/// Each pixel is an RGB555 color, where the least significant bits are red
+pub type SGBFrameBuffer = [u16; SGB_FRAME_WIDTH * SGB_FRAME_HEIGHT];
+
+pub(super) fn empty_frame() -> Box<SGBFrameBuffer> {
+    Box::new([0; SGB_FRAME_WIDTH * SGB_FRAME_HEIGHT])
+}
+
+/// Top-left corner of the Game Boy screen in the picture
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+/// Returns the 2-bit shade of given pixel in the Game Boy display buffer
+fn display_pixel(display: &DisplayBuffer, x: usize, y: usize) -> u8 {
+    let i = y * 160 + x;
+    ((display[i / 16] >> (2 * (i % 16))) & 0b11) as u8
+}
+
+impl SGB {
+    /// Returns the latest composed picture
+    pub fn frame(&self) -> &SGBFrameBuffer {
+        &self.frame
+    }
+
+    /// Handles a finished Game Boy frame: runs a requested VRAM transfer from it
+    /// and draws the picture
+    pub fn frame_finished(&mut self, display: &DisplayBuffer) {
+        if let Some(transfer) = self.transfer.take() {
+            self.run_transfer(transfer, &Self::transfer_data(display));
+        }
+        self.compose(display);
+    }
+
+    /// Reads the 4 KiB sent with a VRAM transfer from the screen.
+    /// Games display the data as 256 tiles in order, which are read back into tile data
+    fn transfer_data(display: &DisplayBuffer) -> Vec<u8> {
+        let mut data = Vec::with_capacity(0x1000);
+        for tile in 0..256 {
+            let (tile_x, tile_y) = (8 * (tile % 20), 8 * (tile / 20));
+            for row in 0..8 {
+                let (mut low, mut high) = (0, 0);
+                for x in 0..8 {
+                    let shade = display_pixel(display, tile_x + x, tile_y + row);
+                    low |= (shade & 1) << (7 - x);
+                    high |= (shade >> 1) << (7 - x);
+                }
+                data.extend([low, high]);
+            }
+        }
+        data
+    }
+
+    /// Stores data sent with a VRAM transfer
+    fn run_transfer(&mut self, transfer: Transfer, data: &[u8]) {
+        let words = || {
+            data.chunks(2)
+                .map(|word| u16::from_le_bytes([word[0], word[1]]))
+        };
+        match transfer {
+            Transfer::Palettes => {
+                let colors: Vec<u16> = words().map(|color| color & 0x7FFF).collect();
+                for (palette, colors) in self.system_palettes.iter_mut().zip(colors.chunks(4)) {
+                    palette.copy_from_slice(colors);
+                }
+            }
+            Transfer::BorderTiles { upper } => {
+                let start = if upper { 0x1000 } else { 0 };
+                self.border_tiles[start..start + 0x1000].copy_from_slice(data);
+            }
+            Transfer::BorderMap => {
+                // Tile map of 32x32 entries, of which only 28 rows are visible,
+                // followed by palettes 4-7
+                for (entry, value) in self.border_map.iter_mut().zip(words()) {
+                    *entry = value;
+                }
+                let colors: Vec<u16> = words().skip(0x400).map(|color| color & 0x7FFF).collect();
+                for (palette, colors) in self.border_palettes.iter_mut().zip(colors.chunks(16)) {
+                    palette.copy_from_slice(colors);
+                }
+            }
+            Transfer::AttributeFiles => {
+                let length = self.attribute_files.len();
+                self.attribute_files.copy_from_slice(&data[..length]);
+            }
+        }
+    }
+
+    /// Returns the color of the border at given pixel, or None if it's transparent
+    fn border_color(&self, x: usize, y: usize) -> Option<u16> {
+        let entry = self.border_map[(y / 8) * 32 + x / 8];
+        let tile = usize::from(entry & 0xFF);
+        let palette = usize::from((entry >> 10) & 0b111);
+        let column = if entry & 0x4000 > 0 { x % 8 } else { 7 - x % 8 };
+        let row = if entry & 0x8000 > 0 { 7 - y % 8 } else { y % 8 };
+        // Rows are stored as two pairs of bit planes
+        let data = &self.border_tiles[32 * tile..32 * (tile + 1)];
+        let color = (0..4).fold(0, |color, plane| {
+            let byte = data[16 * (plane / 2) + 2 * row + plane % 2];
+            color | (((byte >> column) & 1) << plane)
+        });
+        // Border can only use palettes 4-7, and color 0 is transparent
+        (color != 0 && palette >= 4).then(|| self.border_palettes[palette - 4][color as usize])
+    }
+
+    /// Draws the Game Boy screen with its palettes inside the border
+    fn compose(&mut self, display: &DisplayBuffer) {
+        let backdrop = self.palettes[0][0];
+        for y in 0..SGB_FRAME_HEIGHT {
+            for x in 0..SGB_FRAME_WIDTH {
+                let i = y * SGB_FRAME_WIDTH + x;
+                if let Some(color) = self.border_color(x, y) {
+                    self.frame[i] = color;
+                    continue;
+                }
+                let (screen_x, screen_y) = (x.wrapping_sub(SCREEN_X), y.wrapping_sub(SCREEN_Y));
+                if screen_x >= 160 || screen_y >= 144 {
+                    self.frame[i] = backdrop;
+                    continue;
+                }
+                self.frame[i] = match self.mask {
+                    // Frozen screen keeps the pixels of the previous frame
+                    ScreenMask::Freeze => continue,
+                    ScreenMask::Black => 0,
+                    ScreenMask::Color0 => backdrop,
+                    ScreenMask::None => {
+                        let palette = self.attributes[(screen_y / 8) * 20 + screen_x / 8];
+                        let shade = display_pixel(display, screen_x, screen_y);
+                        self.palettes[usize::from(palette)][usize::from(shade)]
+                    }
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::*;
+
+mod attributes;
+mod frame;
+pub use frame::*;
+
+/// What the Game Boy screen shows, set with MASK_EN so games can hide their screen while
+/// preparing it
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum ScreenMask {
+    /// Screen is drawn normally
+    None,
+    /// Screen keeps showing the last frame
+    Freeze,
+    /// Screen is black
+    Black,
+    /// Screen is filled with color 0
+    Color0,
+}
+
+/// Data of the next frame to copy to the SNES with a *_TRN command
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// PAL_TRN: system palettes
+    Palettes,
+    /// CHR_TRN: lower or upper half of the border tiles
+    BorderTiles { upper: bool },
+    /// PCT_TRN: border tile map and palettes
+    BorderMap,
+    /// ATTR_TRN: attribute files
+    AttributeFiles,
+}
+
+/// Super Game Boy, which receives command packets from the Game Boy through the joypad register
+/// and draws its screen with colors inside a border
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Deserialize, Serialize)]
+pub struct SGB {
+    /// Packet being received
+    packet: [u8; 16],
+    /// Amount of bits received of the current packet, or None if not receiving one
+    packet_bits: Option<u8>,
+    /// P14 and P15 lines on the previous write to the joypad register
+    last_lines: u8,
+    /// Data of the command being received, which can take up to seven packets
+    command: Vec<u8>,
+    /// Packets left to receive for the current command
+    packets_left: u8,
+    /// Mask of the joypad index, which is the amount of joypads enabled with MLT_REQ minus one
+    player_mask: u8,
+    /// Joypad currently read through the joypad register
+    player: u8,
+    /// Palettes 0-3 used for the Game Boy screen, as RGB555 colors
+    palettes: [[u16; 4]; 4],
+    /// 512 system palettes set with PAL_TRN
+    system_palettes: Vec<[u16; 4]>,
+    /// 45 attribute files of 90 bytes set with ATTR_TRN
+    attribute_files: Vec<u8>,
+    /// Palette of each 8x8 tile on the Game Boy screen
+    #[serde(with = "BigArray")]
+    attributes: [u8; 20 * 18],
+    mask: ScreenMask,
+    /// Border tile data in the SNES 4 bits per pixel format
+    border_tiles: Vec<u8>,
+    /// 32x28 border tile map entries
+    border_map: Vec<u16>,
+    /// Border palettes 4-7
+    border_palettes: [[u16; 16]; 4],
+    /// VRAM transfer waiting for the next frame
+    transfer: Option<Transfer>,
+    #[serde(skip)]
+    #[serde(default = "empty_frame")]
+    frame: Box<SGBFrameBuffer>,
+}
+
+impl SGB {
+    /// Palette 1-A, which is used until the game sets its own palettes
+    const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+    pub fn new() -> Self {
+        Self {
+            packet: [0; 16],
+            packet_bits: None,
+            last_lines: 0x30,
+            command: Vec::with_capacity(7 * 16),
+            packets_left: 0,
+            player_mask: 0,
+            player: 0,
+            palettes: [Self::DEFAULT_PALETTE; 4],
+            system_palettes: vec![[0; 4]; 512],
+            attribute_files: vec![0; 45 * 90],
+            attributes: [0; 20 * 18],
+            mask: ScreenMask::None,
+            border_tiles: vec![0; 256 * 32],
+            border_map: vec![0; 32 * 28],
+            border_palettes: [[0; 16]; 4],
+            transfer: None,
+            frame: empty_frame(),
+        }
+    }
+
+    /// Handles a write to the joypad register, where pulses on P14 and P15 send packets.
+    /// Both lines low starts a packet, then P14 low sends 0 and P15 low sends 1,
+    /// with both lines high in between bits
+    pub fn write_joypad(&mut self, value: u8) {
+        let lines = value & 0x30;
+        match lines {
+            0x00 => {
+                self.packet = [0; 16];
+                self.packet_bits = Some(0);
+            }
+            0x10 | 0x20 if self.last_lines == 0x30 => {
+                if let Some(bits) = self.packet_bits {
+                    let bit = u8::from(lines == 0x10);
+                    self.packet[usize::from(bits / 8)] |= bit << (bits % 8);
+                    // Stop bit after the 128 data bits is ignored
+                    if bits + 1 == 128 {
+                        self.packet_bits = None;
+                        self.receive_packet();
+                    } else {
+                        self.packet_bits = Some(bits + 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+        // Next joypad is selected when P15 goes high
+        if self.last_lines & 0x20 == 0 && lines & 0x20 > 0 {
+            self.player = (self.player + 1) & self.player_mask;
+        }
+        self.last_lines = lines;
+    }
+
+    /// Returns the value of the joypad register, where the joypad index is read while
+    /// neither line is selected. Joypads other than the first are never pressed
+    pub fn read_joypad(&self, input: &InputReg) -> u8 {
+        let value = input.mem_read(0xFF00);
+        if !input.select_button && !input.select_dpad {
+            (value & 0xF0) | (0x0F - self.player)
+        } else if self.player != 0 {
+            value | 0x0F
+        } else {
+            value
+        }
+    }
+
+    /// Adds a received packet to the current command, running it once all packets are received
+    fn receive_packet(&mut self) {
+        if self.packets_left == 0 {
+            self.command.clear();
+            // Lowest 3 bits of the first byte tell the amount of packets in the command
+            self.packets_left = (self.packet[0] & 0b111).max(1);
+        }
+        self.command.extend_from_slice(&self.packet);
+        self.packets_left -= 1;
+        if self.packets_left == 0 {
+            self.run_command();
+        }
+    }
+
+    /// Runs the received command
+    fn run_command(&mut self) {
+        let data = std::mem::take(&mut self.command);
+        match data[0] >> 3 {
+            // PAL01, PAL23, PAL03, PAL12
+            0x00 => self.set_palettes(&data, 0, 1),
+            0x01 => self.set_palettes(&data, 2, 3),
+            0x02 => self.set_palettes(&data, 0, 3),
+            0x03 => self.set_palettes(&data, 1, 2),
+            // ATTR_BLK, ATTR_LIN, ATTR_DIV, ATTR_CHR
+            0x04 => self.attribute_blocks(&data),
+            0x05 => self.attribute_lines(&data),
+            0x06 => self.attribute_divide(&data),
+            0x07 => self.attribute_characters(&data),
+            // PAL_SET
+            0x0A => {
+                for (i, palette) in data[1..9].chunks(2).enumerate() {
+                    let index = usize::from(u16::from_le_bytes([palette[0], palette[1]]) & 0x1FF);
+                    self.palettes[i] = self.system_palettes[index];
+                }
+                // Color 0 is shared by all palettes
+                for i in 1..4 {
+                    self.palettes[i][0] = self.palettes[0][0];
+                }
+                if data[9] & 0x80 > 0 {
+                    self.apply_attribute_file(data[9] & 0x3F);
+                }
+                if data[9] & 0x40 > 0 {
+                    self.mask = ScreenMask::None;
+                }
+            }
+            // PAL_TRN
+            0x0B => self.transfer = Some(Transfer::Palettes),
+            // MLT_REQ: 1, 2 or 4 joypads
+            0x11 => {
+                self.player_mask = match data[1] & 0b11 {
+                    1 => 1,
+                    3 => 3,
+                    _ => 0,
+                };
+                self.player = 0;
+            }
+            // CHR_TRN
+            0x13 => {
+                self.transfer = Some(Transfer::BorderTiles {
+                    upper: data[1] & 1 > 0,
+                })
+            }
+            // PCT_TRN
+            0x14 => self.transfer = Some(Transfer::BorderMap),
+            // ATTR_TRN
+            0x15 => self.transfer = Some(Transfer::AttributeFiles),
+            // ATTR_SET
+            0x16 => {
+                self.apply_attribute_file(data[1] & 0x3F);
+                if data[1] & 0x40 > 0 {
+                    self.mask = ScreenMask::None;
+                }
+            }
+            // MASK_EN
+            0x17 => {
+                self.mask = match data[1] & 0b11 {
+                    1 => ScreenMask::Freeze,
+                    2 => ScreenMask::Black,
+                    3 => ScreenMask::Color0,
+                    _ => ScreenMask::None,
+                }
+            }
+            command => log::debug!("Ignored unsupported SGB command {command:#04X}"),
+        }
+    }
+
+    /// Sets the colors of two palettes from a PALxx command.
+    /// The first color is shared by all palettes
+    fn set_palettes(&mut self, data: &[u8], first: usize, second: usize) {
+        let colors: Vec<u16> = data[1..15]
+            .chunks(2)
+            .map(|color| u16::from_le_bytes([color[0], color[1]]) & 0x7FFF)
+            .collect();
+        for palette in &mut self.palettes {
+            palette[0] = colors[0];
+        }
+        self.palettes[first][1..4].copy_from_slice(&colors[1..4]);
+        self.palettes[second][1..4].copy_from_slice(&colors[4..7]);
+    }
+}
@@ -25,6 +25,16 @@ pub struct Timer {
     pub previous_and: bool,
     /// Cycles left in the simulated delay after overflowing.
     pub overflow_delay: i8,
+    /// T-cycles left in the M-cycle TMA was reloaded into TIMA,
+    /// during which writes to TIMA are ignored and writes to TMA also go to TIMA
+    reload_cycles: u8,
+    /// Index of the divider bit that clocks DIV-APU, which is one higher in double speed mode
+    pub div_apu_bit: u8,
+    /// State of the DIV-APU bit on the previous check
+    previous_div_apu: bool,
+    /// Set on the falling edge of the DIV-APU bit, including ones caused by resetting DIV.
+    /// This is set back to false when handled on APU
+    pub div_apu_tick: bool,
 }
 
 impl Timer {
@@ -39,12 +49,17 @@ impl Timer {
             previous_and: false,
             request_interrupt: false,
             overflow_delay: 0,
+            reload_cycles: 0,
+            div_apu_bit: 12,
+            previous_div_apu: false,
+            div_apu_tick: false,
         }
     }
 
     // Cycles the timer forward by one T-cycle
     pub fn cycle(&mut self) {
         self.request_interrupt = false;
+        self.reload_cycles = self.reload_cycles.saturating_sub(1);
 
         // Simulate the 4 T-cycle delay after overflowing
         // before TMA is written to TIMA and interrupt is requested
@@ -52,6 +67,7 @@ impl Timer {
             if self.overflow_delay == 0 {
                 self.tima = self.tma;
                 self.request_interrupt = true;
+                self.reload_cycles = 4;
             }
             self.overflow_delay -= 1;
         }
@@ -60,13 +76,19 @@ impl Timer {
         // DIV register only maps to the upper 8 bits,
         // so to the software its incremented only every 256 dots
         self.div = self.div.wrapping_add(1);
+        self.detect_edges();
+    }
 
+    /// Checks the divider bits for falling edges, which increment TIMA and clock DIV-APU.
+    /// Besides the divider counting, edges are caused by writes to DIV and TAC
+    fn detect_edges(&mut self) {
         // Get the AND value from selected DIV bit and the enabled flag
         let div_val = (self.div >> (self.div_bit)) & 0b1;
         let and = (self.enabled as u16) & div_val > 0;
 
         // TIMA is incremented on falling edge (previous AND = true, current AND = false)
-        // This means that setting the enabled flag can also trigger a TIMA increment
+        // This means that disabling the timer or selecting another bit can also trigger
+        // a TIMA increment
         if !and && self.previous_and {
             let overflow: bool;
             (self.tima, overflow) = self.tima.overflowing_add(1);
@@ -75,8 +97,13 @@ impl Timer {
                 self.overflow_delay = 3;
             }
         }
-
         self.previous_and = and;
+
+        let div_apu = (self.div >> self.div_apu_bit) & 1 > 0;
+        if !div_apu && self.previous_div_apu {
+            self.div_apu_tick = true;
+        }
+        self.previous_div_apu = div_apu;
     }
 }
 
@@ -86,21 +113,33 @@ impl MemoryAccess for Timer {
             0xFF04 => (self.div >> 8) as u8,
             0xFF05 => self.tima,
             0xFF06 => self.tma,
-            0xFF07 => self.control.bits(),
+            // Unused bits of TAC read as 1
+            0xFF07 => 0b1111_1000 | self.control.bits(),
             _ => unreachable!(),
         }
     }
 
     fn mem_write(&mut self, address: u16, value: u8) {
         match address {
-            0xFF04 => self.div = 0,
+            0xFF04 => {
+                self.div = 0;
+                self.detect_edges();
+            }
+            // TIMA written on the same M-cycle TMA is reloaded keeps the reloaded value
+            0xFF05 if self.reload_cycles > 0 => {}
             0xFF05 => {
                 self.tima = value;
                 // Writing to TIMA during the delay period after overflowing
                 // prevents TMA being written to TIMA and sending interrupt
                 self.overflow_delay = -1;
             }
-            0xFF06 => self.tma = value,
+            0xFF06 => {
+                self.tma = value;
+                // TMA written on the same M-cycle it's reloaded is reloaded again
+                if self.reload_cycles > 0 {
+                    self.tima = value;
+                }
+            }
             0xFF07 => {
                 self.control = TimerControl::from_bits_truncate(value);
                 self.enabled = self.control.intersects(TimerControl::ENABLE);
@@ -110,7 +149,8 @@ impl MemoryAccess for Timer {
                     0b10 => 5,
                     0b11 => 7,
                     _ => unreachable!(),
-                }
+                };
+                self.detect_edges();
             }
             _ => unreachable!(),
         }
@@ -1,6 +1,8 @@
-use dmg_2025_core::CPU;
+use dmg_2025_core::{
+    AudioChannel, AudioRecording, CPU, HardwareConfig, Model, TcpLink, WavFormat, WavWriter,
+};
 use dotenv::dotenv;
-use std::{env, error, fs};
+use std::{env, error, fs, io::BufWriter, path::Path};
 
 pub fn main() -> Result<(), Box<dyn error::Error + 'static>> {
     dotenv().expect("No .env file found in working directory");
@@ -9,12 +11,171 @@ pub fn main() -> Result<(), Box<dyn error::Error + 'static>> {
     let rom_path = env::var("ROM_PATH")?;
     let rom = fs::read(rom_path)?;
 
-    let mut cpu = CPU::new(rom).unwrap();
+    // Optional boot ROM to run before the cartridge
+    let boot_rom = match env::var("BOOT_ROM_PATH") {
+        Ok(path) => Some(fs::read(path)?),
+        Err(_) => None,
+    };
+
+    // Hardware model to emulate, e.g. MODEL=SGB.
+    // By default it's chosen based on the cartridge
+    let model = match env::var("MODEL") {
+        Ok(name) => Some(name.parse::<Model>()?),
+        Err(_) => None,
+    };
+
+    let config = HardwareConfig { boot_rom, model };
+    let mut cpu = if rom.starts_with(b"GBS") {
+        let mut cpu = CPU::from_gbs(rom, config)?;
+        // Song of the GBS file to play counting from 0, e.g. TRACK=3.
+        // By default it's the first song given in the file
+        if let Ok(track) = env::var("TRACK") {
+            cpu.select_gbs_track(track.parse()?);
+        }
+        if let Some(header) = cpu.get_gbs_header() {
+            println!(
+                "Playing \"{}\" by {}, track {} of {}",
+                header.title,
+                header.author,
+                cpu.get_gbs_track().unwrap_or_default() + 1,
+                header.song_count
+            );
+        }
+        cpu
+    } else {
+        CPU::with_config(rom, config)?
+    };
     cpu.set_audio_sample_rate(44100);
+
+    // Link cable session with another debugger instance, e.g. LINK_HOST=127.0.0.1:8765
+    // on one instance and LINK_JOIN=127.0.0.1:8765 on the other
+    if let Ok(address) = env::var("LINK_HOST") {
+        println!("Waiting for link cable connection at {address}");
+        cpu.set_serial_device(Box::new(TcpLink::host(&address)?));
+        println!("Link cable connected");
+    } else if let Ok(address) = env::var("LINK_JOIN") {
+        cpu.set_serial_device(Box::new(TcpLink::join(&address)?));
+        println!("Link cable connected to {address}");
+    }
+
+    // Audio recording into a WAV file, e.g. RECORD_WAV=out.wav.
+    // RECORD_FORMAT is PCM16 (default) or Float32, RECORD_SAMPLE_RATE defaults to 48000,
+    // and RECORD_STEMS=1 also writes each channel into its own file next to the mix
+    let mut recorder = match env::var("RECORD_WAV") {
+        Ok(path) => {
+            let format = match env::var("RECORD_FORMAT") {
+                Ok(name) => name.parse::<WavFormat>()?,
+                Err(_) => WavFormat::PCM16,
+            };
+            let sample_rate = match env::var("RECORD_SAMPLE_RATE") {
+                Ok(rate) => rate.parse::<u32>()?,
+                Err(_) => 48000,
+            };
+            let stems = env::var("RECORD_STEMS").is_ok_and(|value| value == "1");
+            let recording = AudioRecording::new(sample_rate, stems);
+            cpu.start_audio_recording(recording.clone());
+            Some(WavRecorder::create(Path::new(&path), recording, format)?)
+        }
+        Err(_) => None,
+    };
+    // Emulated time after which the debugger exits, e.g. RECORD_SECONDS=60.
+    // Emulation isn't throttled, so recordings render faster than real time
+    let duration = match env::var("RECORD_SECONDS") {
+        Ok(seconds) => Some(seconds.parse::<f32>()? * 1000.0),
+        Err(_) => None,
+    };
+
     let mut elapsed = 0.0;
     loop {
         cpu.run(time);
         elapsed += time;
-        println!("Ran CPU for {elapsed} ms")
+        if let Some(recorder) = &mut recorder {
+            recorder.write()?;
+        }
+        println!(
+            "Ran CPU for {elapsed} ms, {} VRAM/OAM accesses blocked",
+            cpu.get_blocked_accesses()
+        );
+        if duration.is_some_and(|duration| elapsed >= duration) {
+            if let Some(recorder) = recorder {
+                recorder.finish()?;
+            }
+            return Ok(());
+        }
+    }
+}
+
+type WavFile = WavWriter<BufWriter<fs::File>>;
+
+/// Writes an audio recording into WAV files as it fills
+struct WavRecorder {
+    recording: AudioRecording,
+    mix: WavFile,
+    stems: Option<[WavFile; 4]>,
+}
+
+impl WavRecorder {
+    /// Creates the WAV file of the mix at given path, and the stems next to it if recorded
+    fn create(
+        path: &Path,
+        recording: AudioRecording,
+        format: WavFormat,
+    ) -> Result<Self, Box<dyn error::Error + 'static>> {
+        let sample_rate = recording.get_sample_rate();
+        let create = |path: &Path, channels: u16| {
+            WavWriter::new(
+                BufWriter::new(fs::File::create(path)?),
+                sample_rate,
+                channels,
+                format,
+            )
+        };
+        let mix = create(path, 2)?;
+        let stems = if recording.has_stems() {
+            let stem = |channel: AudioChannel| {
+                let name = match channel {
+                    AudioChannel::Square1 => "square1",
+                    AudioChannel::Square2 => "square2",
+                    AudioChannel::Wave => "wave",
+                    AudioChannel::Noise => "noise",
+                };
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                create(&path.with_file_name(format!("{stem}-{name}.wav")), 1)
+            };
+            Some([
+                stem(AudioChannel::Square1)?,
+                stem(AudioChannel::Square2)?,
+                stem(AudioChannel::Wave)?,
+                stem(AudioChannel::Noise)?,
+            ])
+        } else {
+            None
+        };
+        Ok(Self {
+            recording,
+            mix,
+            stems,
+        })
+    }
+
+    /// Writes the samples recorded since the last call
+    fn write(&mut self) -> std::io::Result<()> {
+        let recorded = self.recording.take();
+        self.mix.write_samples(&recorded.mix)?;
+        if let Some(stems) = &mut self.stems {
+            for (file, samples) in stems.iter_mut().zip(&recorded.stems) {
+                file.write_samples(samples)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the final sizes into the headers of the files
+    fn finish(self) -> std::io::Result<()> {
+        self.mix.finish()?;
+        for file in self.stems.into_iter().flatten() {
+            file.finish()?;
+        }
+        Ok(())
     }
 }
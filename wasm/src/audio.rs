@@ -2,7 +2,7 @@ use cpal::{
     FromSample, SizedSample, Stream,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use dmg_2025_core::AudioBufferConsumer;
+use dmg_2025_core::{AudioBufferConsumer, WavFormat, WavWriter};
 use ringbuf::traits::Consumer;
 use std::sync::{Arc, RwLock};
 
@@ -102,3 +102,20 @@ impl AudioHandler {
         self.stream = Some(stream);
     }
 }
+
+/// Encodes interleaved samples into a WAV file
+pub fn encode_wav(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    format: WavFormat,
+) -> std::io::Result<Vec<u8>> {
+    let mut writer = WavWriter::new(
+        std::io::Cursor::new(Vec::new()),
+        sample_rate,
+        channels,
+        format,
+    )?;
+    writer.write_samples(samples)?;
+    Ok(writer.finish()?.into_inner())
+}
@@ -14,6 +14,8 @@ mod audio;
 use audio::*;
 mod renderer;
 use renderer::*;
+mod printer;
+use printer::*;
 mod proxy;
 use proxy::*;
 
@@ -36,6 +38,11 @@ pub fn spawn_event_loop() -> Result<Proxy, JsValue> {
     Ok(Proxy { proxy })
 }
 
+/// Returns the current host time as a Unix timestamp in seconds
+fn unix_timestamp() -> u64 {
+    (web_sys::js_sys::Date::now() / 1000.0) as u64
+}
+
 pub struct App {
     proxy: Option<winit::event_loop::EventLoopProxy<UserEvent>>,
     renderer: Option<Renderer>,
@@ -45,6 +52,16 @@ pub struct App {
     cpu: Option<CPU>,
     rom: Vec<u8>,
     last_cpu_frame: u8,
+    /// Queue of prints from the connected printer, if any
+    prints: Option<PrintQueue>,
+    /// Boot ROM to run when loading ROMs
+    boot_rom: Option<Vec<u8>>,
+    /// Hardware model to emulate when loading ROMs, chosen based on the cartridge if not set
+    model: Option<Model>,
+    /// Muted and soloed state of each sound channel, in the order of `AudioChannel::ALL`
+    audio_channels: [(bool, bool); 4],
+    /// Audio recording in progress, if any
+    recording: Option<AudioRecording>,
 }
 
 impl App {
@@ -58,6 +75,38 @@ impl App {
             cpu: None,
             rom: vec![],
             last_cpu_frame: 0,
+            prints: None,
+            boot_rom: None,
+            model: None,
+            audio_channels: [(false, false); 4],
+            recording: None,
+        }
+    }
+
+    /// Connects a new printer to the serial port of the CPU if printer is enabled
+    fn connect_printer(&mut self) {
+        let Some(cpu) = &mut self.cpu else {
+            return;
+        };
+        if self.prints.is_some() {
+            let printer = Printer::new();
+            self.prints = Some(printer.queue());
+            cpu.set_serial_device(Box::new(printer));
+        }
+    }
+
+    /// Applies the muted and soloed sound channels to the CPU
+    fn apply_audio_channels(&mut self) {
+        let Some(cpu) = &mut self.cpu else {
+            return;
+        };
+        for (channel, (muted, solo)) in AudioChannel::ALL.into_iter().zip(self.audio_channels) {
+            cpu.set_audio_channel_muted(channel, muted);
+            cpu.set_audio_channel_solo(channel, solo);
+        }
+        // Recording continues across loaded ROMs and save states
+        if let Some(recording) = &self.recording {
+            cpu.start_audio_recording(recording.clone());
         }
     }
 
@@ -67,14 +116,29 @@ impl App {
         rom.hash(&mut hasher);
         let hash = hasher.finish32();
 
-        match CPU::new(rom) {
+        let config = HardwareConfig {
+            boot_rom: self.boot_rom.clone(),
+            model: self.model,
+        };
+        // GBS music files are played instead of run as a cartridge
+        let result = if rom.starts_with(b"GBS") {
+            CPU::from_gbs(rom, config)
+        } else {
+            CPU::with_config(rom, config)
+        };
+        match result {
             Ok(mut cpu) => {
                 // Gather info about loaded ROM
                 let info = cpu.get_cartridge_info();
+                let gbs = cpu.get_gbs_header();
                 let rom_info = ROMInfo {
                     title: info.title.clone(),
-                    should_be_saved: info.has_ram && info.has_battery,
+                    should_be_saved: (info.has_ram || info.has_timer) && info.has_battery,
                     hash,
+                    author: gbs.map(|header| header.author.clone()),
+                    copyright: gbs.map(|header| header.copyright.clone()),
+                    track_count: gbs.map(|header| header.song_count),
+                    track: cpu.get_gbs_track(),
                 };
 
                 // Initialize audio playback
@@ -83,6 +147,8 @@ impl App {
                     cpu.init_audio_buffer(self.audio.sample_capacity, self.audio.channels);
                 self.audio.init_playback(audio_consumer);
                 self.cpu = Some(cpu);
+                self.connect_printer();
+                self.apply_audio_channels();
                 self.renderer.as_ref().unwrap().window.request_redraw();
 
                 Ok(rom_info)
@@ -141,7 +207,13 @@ impl ApplicationHandler<UserEvent> for App {
                 if let Some(cpu) = &self.cpu {
                     // Update buffer only when there is new frame available
                     if self.last_cpu_frame != cpu.frame_counter {
-                        renderer.update_display(cpu.get_display_buffer());
+                        if let Some(frame) = cpu.get_sgb_frame_buffer() {
+                            renderer.update_sgb_display(frame);
+                        } else if let Some(display) = cpu.get_color_display_buffer() {
+                            renderer.update_color_display(display);
+                        } else {
+                            renderer.update_display(cpu.get_display_buffer());
+                        }
                         self.last_cpu_frame = cpu.frame_counter;
                     }
 
@@ -188,10 +260,10 @@ impl ApplicationHandler<UserEvent> for App {
                                 // Loop through files in zip to find ROM
                                 for i in 0..archive.len() {
                                     if let Ok(archive_file) = archive.by_index(i) {
-                                        // Choose first file inside zip that either has no extension or .gb
+                                        // Choose first file inside zip that either has no extension, .gb or .gbs
                                         if Path::new(archive_file.name())
                                             .extension()
-                                            .is_none_or(|ext| ext == "gb")
+                                            .is_none_or(|ext| ext == "gb" || ext == "gbs")
                                         {
                                             let buf = BufReader::new(archive_file);
                                             let rom_result: Result<Vec<u8>> = buf.bytes().collect();
@@ -225,6 +297,7 @@ impl ApplicationHandler<UserEvent> for App {
                     Q::LoadRAM { ram } => {
                         if let Some(cpu) = &mut self.cpu {
                             cpu.set_ram(ram);
+                            cpu.sync_rtc(unix_timestamp());
                             log::info!("RAM set");
                             request.resolve();
                         } else {
@@ -233,8 +306,13 @@ impl ApplicationHandler<UserEvent> for App {
                     }
                     Q::RunCPU { millis } => {
                         if let Some(cpu) = &mut self.cpu {
+                            // Keep cartridge clock up to date with time spent paused
+                            cpu.sync_rtc(unix_timestamp());
                             cpu.run(millis);
-                            request.resolve();
+                            match cpu.get_lockup_address() {
+                                Some(address) => request.respond(BridgeResponse::LockedUp(address)),
+                                None => request.resolve(),
+                            }
                         } else {
                             request.reject("CPU not initialized");
                         }
@@ -261,11 +339,14 @@ impl ApplicationHandler<UserEvent> for App {
                     Q::DeserializeCPU { buffer } => match postcard::from_bytes::<CPU>(&buffer) {
                         Ok(mut deserialized) => {
                             deserialized.set_rom(self.rom.clone());
+                            deserialized.sync_rtc(unix_timestamp());
                             deserialized.set_audio_sample_rate(self.audio.sample_rate);
                             let audio_consumer = deserialized
                                 .init_audio_buffer(self.audio.sample_capacity, self.audio.channels);
                             self.audio.init_playback(audio_consumer);
                             self.cpu = Some(deserialized);
+                            self.connect_printer();
+                            self.apply_audio_channels();
                             request.resolve();
                         }
                         Err(e) => request.reject(&format!("Failed to deserialize: {e}")),
@@ -317,6 +398,130 @@ impl ApplicationHandler<UserEvent> for App {
                         self.options = options;
                         request.resolve();
                     }
+                    Q::SetBootROM { boot_rom } => {
+                        // Boot ROM is used the next time a ROM is loaded
+                        self.boot_rom = boot_rom;
+                        request.resolve();
+                    }
+                    Q::SetModel { model } => match model.as_str() {
+                        // Model is used the next time a ROM is loaded
+                        "Auto" => {
+                            self.model = None;
+                            request.resolve();
+                        }
+                        _ => match model.parse() {
+                            Ok(model) => {
+                                self.model = Some(model);
+                                request.resolve();
+                            }
+                            Err(e) => request.reject(&e),
+                        },
+                    },
+                    Q::SetPrinterConnected { connected } => {
+                        if connected {
+                            self.prints = Some(PrintQueue::default());
+                            self.connect_printer();
+                        } else {
+                            self.prints = None;
+                            if let Some(cpu) = &mut self.cpu {
+                                cpu.set_serial_device(Box::new(Disconnected));
+                            }
+                        }
+                        request.resolve();
+                    }
+                    Q::TakePrints {} => {
+                        let prints = self.prints.as_ref().map(|queue| queue.take());
+                        let mut pngs = Vec::new();
+                        for print in prints.unwrap_or_default() {
+                            match encode_print(&print) {
+                                Ok(png) => pngs.push(png),
+                                Err(e) => log::error!("Unable to encode print: {e}"),
+                            }
+                        }
+                        request.respond(BridgeResponse::Prints(pngs));
+                    }
+                    Q::SetAudioChannel {
+                        channel,
+                        muted,
+                        solo,
+                    } => {
+                        let index = match channel.as_str() {
+                            "Square 1" => Some(0),
+                            "Square 2" => Some(1),
+                            "Wave" => Some(2),
+                            "Noise" => Some(3),
+                            _ => None,
+                        };
+                        match index {
+                            Some(index) => {
+                                self.audio_channels[index] = (muted, solo);
+                                self.apply_audio_channels();
+                                request.resolve();
+                            }
+                            None => request.reject(&format!("Unknown audio channel {channel}")),
+                        }
+                    }
+                    Q::SelectTrack { .. } | Q::NextTrack {} | Q::PreviousTrack {} => {
+                        let current = self.cpu.as_ref().and_then(|cpu| {
+                            Some((cpu.get_gbs_track()?, cpu.get_gbs_header()?.song_count))
+                        });
+                        match current {
+                            Some((current, count)) => {
+                                // Next and previous wrap around the ends of the track list
+                                let track = match query {
+                                    Q::SelectTrack { track } => track,
+                                    Q::NextTrack {} if current + 1 < count => current + 1,
+                                    Q::NextTrack {} => 0,
+                                    _ => current.checked_sub(1).unwrap_or(count - 1),
+                                };
+                                if track < count {
+                                    self.cpu.as_mut().unwrap().select_gbs_track(track);
+                                    request.respond(BridgeResponse::Track(track));
+                                } else {
+                                    request.reject(&format!(
+                                        "Track {track} is out of range, GBS file has {count} tracks"
+                                    ));
+                                }
+                            }
+                            None => request.reject("No GBS file loaded"),
+                        }
+                    }
+                    Q::StartRecording { sample_rate, stems } => {
+                        let recording = AudioRecording::new(sample_rate, stems);
+                        if let Some(cpu) = &mut self.cpu {
+                            cpu.start_audio_recording(recording.clone());
+                        }
+                        self.recording = Some(recording);
+                        request.resolve();
+                    }
+                    Q::StopRecording { format } => match self.recording.take() {
+                        Some(recording) => {
+                            if let Some(cpu) = &mut self.cpu {
+                                cpu.stop_audio_recording();
+                            }
+                            // WAV files of the mix and then the stems, if recorded
+                            let result = format.parse().and_then(|format| {
+                                let sample_rate = recording.get_sample_rate();
+                                let recorded = recording.take();
+                                let mut files =
+                                    vec![encode_wav(&recorded.mix, sample_rate, 2, format)];
+                                if recording.has_stems() {
+                                    for stem in &recorded.stems {
+                                        files.push(encode_wav(stem, sample_rate, 1, format));
+                                    }
+                                }
+                                files
+                                    .into_iter()
+                                    .collect::<std::io::Result<Vec<_>>>()
+                                    .map_err(|e| format!("Unable to encode recording: {e}"))
+                            });
+                            match result {
+                                Ok(files) => request.respond(BridgeResponse::Recording(files)),
+                                Err(e) => request.reject(&e),
+                            }
+                        }
+                        None => request.reject("Audio isn't being recorded"),
+                    },
                 }
             }
         }
@@ -0,0 +1,23 @@
+use super::*;
+use image::{GrayImage, ImageFormat};
+use std::io::Cursor;
+
+/// Encodes image printed by the Game Boy Printer as PNG
+pub fn encode_print(print: &PrintedImage) -> Result<Vec<u8>, image::ImageError> {
+    // Darker exposure settings make the lighter shades darker
+    let darkness = (print.exposure as f32 - 0x40 as f32) / 0x40 as f32 * 0.25;
+    let pixels = print
+        .pixels
+        .iter()
+        .map(|&shade| {
+            let value = 1.0 - (shade as f32 / 3.0);
+            (value * (1.0 - darkness)).clamp(0.0, 1.0) * 255.0
+        })
+        .map(|value| value as u8)
+        .collect();
+    let image = GrayImage::from_raw(PRINT_WIDTH as u32, print.height as u32, pixels)
+        .expect("Printed image size doesn't match its pixel count");
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
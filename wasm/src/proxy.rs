@@ -62,6 +62,14 @@ pub struct ROMInfo {
     pub should_be_saved: bool,
     /// The hash of the ROM file
     pub hash: u32,
+    /// Author of the music, if a GBS file is loaded
+    pub(crate) author: Option<String>,
+    /// Copyright of the music, if a GBS file is loaded
+    pub(crate) copyright: Option<String>,
+    /// Amount of songs, if a GBS file is loaded
+    pub track_count: Option<u8>,
+    /// Song playing first, counting from 0, if a GBS file is loaded
+    pub track: Option<u8>,
 }
 
 #[derive(Tsify, Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +107,34 @@ pub enum BridgeQuery {
     UpdateOptions {
         options: EmulatorOptions,
     },
+    SetBootROM {
+        #[tsify(type = "Uint8Array | null")]
+        boot_rom: Option<Vec<u8>>,
+    },
+    SetModel {
+        model: String,
+    },
+    SetPrinterConnected {
+        connected: bool,
+    },
+    TakePrints {},
+    SetAudioChannel {
+        channel: String,
+        muted: bool,
+        solo: bool,
+    },
+    SelectTrack {
+        track: u8,
+    },
+    NextTrack {},
+    PreviousTrack {},
+    StartRecording {
+        sample_rate: u32,
+        stems: bool,
+    },
+    StopRecording {
+        format: String,
+    },
 }
 
 #[wasm_bindgen]
@@ -107,6 +143,16 @@ impl ROMInfo {
     pub fn title(&self) -> String {
         self.title.clone()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn author(&self) -> Option<String> {
+        self.author.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn copyright(&self) -> Option<String> {
+        self.copyright.clone()
+    }
 }
 
 pub enum BridgeResponse {
@@ -118,6 +164,16 @@ pub enum BridgeResponse {
     /// CPU is successfully serialized into a save state,
     /// returns the serialized CPU
     CPUSerialized(Vec<u8>),
+    /// Returns the images printed since last query as PNG files
+    Prints(Vec<Vec<u8>>),
+    /// CPU has locked up on an illegal opcode,
+    /// returns the address of the opcode
+    LockedUp(u16),
+    /// Song of the GBS file is selected,
+    /// returns the song counting from 0
+    Track(u8),
+    /// Audio recording is stopped, returns the mix and then the stems if recorded as WAV files
+    Recording(Vec<Vec<u8>>),
 }
 
 #[derive(Debug)]
@@ -139,6 +195,15 @@ impl BridgeRequest {
                 R::RAMSaved(buffer) => self
                     .resolve
                     .call1(&JsValue::NULL, &js_sys::Uint8Array::new_from_slice(&buffer)),
+                R::Prints(files) | R::Recording(files) => {
+                    let array = js_sys::Array::new();
+                    for file in files {
+                        array.push(&js_sys::Uint8Array::new_from_slice(&file));
+                    }
+                    self.resolve.call1(&JsValue::NULL, &array)
+                }
+                R::LockedUp(address) => self.resolve.call1(&JsValue::NULL, &address.into()),
+                R::Track(track) => self.resolve.call1(&JsValue::NULL, &track.into()),
             }
             .unwrap_throw();
         } else {
@@ -7,11 +7,13 @@ pub struct DisplayOptionsUniform {
 
     pub scanline_strength: f32,
     pub scanline_size: f32,
-    _pad1: [u32; 2],
+    /// Size of the emulated display in pixels
+    pub display_size: [u32; 2],
 
     pub origin: [i32; 2],
     pub scale: u32,
-    _pad2: u32,
+    /// 1 if the display is drawn from the color display texture instead of the 2-bit buffer
+    pub color_mode: u32,
 }
 
 #[repr(C)]
@@ -7,13 +7,15 @@ struct OptionsUniform {
     scanline_strength: f32,
     /// The size of the scanline
     scanline_size: f32,
-    pad1: vec2<u32>,
+    /// The size of the emulated display in pixels
+    display_size: vec2<u32>,
   
     /// The origin of the display in pixel space
     origin: vec2<i32>,
     /// The scale of pixels
     scale: u32,
-    pad2: u32,
+    /// 1 if pixels are read from the color display texture
+    color_mode: u32,
 }
 
 @group(0) @binding(0)
@@ -30,6 +32,11 @@ struct DisplayUniform {
 @group(1) @binding(0)
 var<uniform> display: DisplayUniform;
 
+// Contains the display pixels in CGB mode or the SGB picture,
+// which don't fit in a uniform buffer
+@group(2) @binding(0)
+var color_display: texture_2d<f32>;
+
 struct VertexOutput {
     @builtin(position) pos: vec4<f32>,
 }
@@ -63,9 +70,12 @@ fn get_pixel_color(pos: vec2<i32>) -> vec4<f32> {
     }
     let pixel = vec2u(pos - origin) / scale;
     // Crop out pixels on the bottom and right sides of display
-    if pixel.x >= 160u || pixel.y >= 144u {
+    if pixel.x >= options.display_size.x || pixel.y >= options.display_size.y {
         return vec4f(0.0);
     }
+    if options.color_mode == 1u {
+        return textureLoad(color_display, pixel, 0);
+    }
     // Calculate index of pixel on display
     let pixel_i = pixel.y * 160u + pixel.x;
     // Calculate index of the two color bits in display buffer
@@ -107,10 +117,10 @@ fn get_scanline_color(color: vec4<f32>, pos: vec2<i32>) -> vec4<f32> {
     // Correct pos so the right and bottom sides show
     // the fully lit scanline
     var corrected_pos = pos;
-    if pos.x == 160 * i32(options.scale) - 1 {
+    if pos.x == i32(options.display_size.x * options.scale) - 1 {
         corrected_pos.x += 1;
     }
-    if pos.y == 144 * i32(options.scale) - 1 {
+    if pos.y == i32(options.display_size.y * options.scale) - 1 {
         corrected_pos.y += 1;
     }
 
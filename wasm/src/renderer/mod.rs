@@ -1,7 +1,10 @@
 use wgpu::util::DeviceExt;
 
 use super::*;
-use dmg_2025_core::{DISPLAY_BUFFER_SIZE, DisplayBuffer};
+use dmg_2025_core::{
+    ColorDisplayBuffer, DISPLAY_BUFFER_SIZE, DisplayBuffer, SGB_FRAME_HEIGHT, SGB_FRAME_WIDTH,
+    SGBFrameBuffer,
+};
 
 mod buffers;
 use buffers::*;
@@ -20,6 +23,7 @@ pub struct Renderer {
     final_render_pipeline: wgpu::RenderPipeline,
 
     display_texture: Option<Texture>,
+    color_display_texture: Texture,
     h_blur_texture: Option<Texture>,
     v_blur_texture: Option<Texture>,
     background_texture: Texture,
@@ -35,6 +39,15 @@ pub struct Renderer {
 }
 
 impl Renderer {
+    /// Size of the Game Boy display
+    const DISPLAY_SIZE: [u32; 2] = [160, 144];
+    /// Size of the color display texture, which fits the Super Game Boy picture
+    const COLOR_TEXTURE_SIZE: wgpu::Extent3d = wgpu::Extent3d {
+        width: SGB_FRAME_WIDTH as u32,
+        height: SGB_FRAME_HEIGHT as u32,
+        depth_or_array_layers: 1,
+    };
+
     fn init_render_pipeline(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
@@ -138,18 +151,9 @@ impl Renderer {
             desired_maximum_frame_latency: 2,
         };
 
-        let options = UniformBuffer::<DisplayOptionsUniform>::new(&device, "Options");
+        let mut options = UniformBuffer::<DisplayOptionsUniform>::new(&device, "Options");
+        options.display_size = Self::DISPLAY_SIZE;
         let display = UniformBuffer::<DisplayBufferUniform>::new(&device, "Display");
-        // Initialize render pipeline for rendering the raw display data
-        let display_shader = device.create_shader_module(wgpu::include_wgsl!("display.wgsl"));
-        let display_render_pipeline = Self::init_render_pipeline(
-            &device,
-            &config,
-            &display_shader,
-            &[&display.bind_group_layout, &options.bind_group_layout],
-        );
-
-        let blur_options = UniformBuffer::<BlurOptionsUniform>::new(&device, "Effect Options");
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
@@ -174,6 +178,28 @@ impl Renderer {
                 ],
                 label: Some("Texture Bind Group Layout"),
             });
+        let color_display_texture = Texture::new(
+            &device,
+            &texture_bind_group_layout,
+            &Self::COLOR_TEXTURE_SIZE,
+            "Color Display Texture",
+        );
+        // Initialize render pipeline for rendering the raw display data
+        let display_shader = device.create_shader_module(wgpu::include_wgsl!("display.wgsl"));
+        let display_render_pipeline = Self::init_render_pipeline(
+            &device,
+            &config,
+            &display_shader,
+            &[
+                &display.bind_group_layout,
+                &options.bind_group_layout,
+                // Color display texture
+                &texture_bind_group_layout,
+            ],
+        );
+
+        let blur_options = UniformBuffer::<BlurOptionsUniform>::new(&device, "Effect Options");
+
         // Initialize render pipeline for blurring shader used for glow effect
         let blur_shader = device.create_shader_module(wgpu::include_wgsl!("blur.wgsl"));
         let blur_render_pipeline = Self::init_render_pipeline(
@@ -252,6 +278,7 @@ impl Renderer {
             final_render_pipeline,
 
             display_texture: None,
+            color_display_texture,
             h_blur_texture: None,
             v_blur_texture: None,
             background_texture,
@@ -304,6 +331,7 @@ impl Renderer {
         display_render_pass.set_pipeline(&self.display_render_pipeline);
         display_render_pass.set_bind_group(0, &self.display_options.bind_group, &[]);
         display_render_pass.set_bind_group(1, &self.display.bind_group, &[]);
+        display_render_pass.set_bind_group(2, &self.color_display_texture.bind_group, &[]);
         display_render_pass.draw(0..6, 0..1);
         drop(display_render_pass);
 
@@ -442,11 +470,12 @@ impl Renderer {
 
             // Calculate pixel scale as the possible largest integer scale
             // which still fits display in both dimensions
-            let mut scale = (width / 160).min(height / 144);
+            let [display_width, display_height] = self.display_options.display_size;
+            let mut scale = (width / display_width).min(height / display_height);
             // Apply option offset to scale
             scale = (scale.saturating_add_signed(self.scale_offset)).max(1);
             // Calculate size of the display
-            let display_size = [160 * scale, 144 * scale];
+            let display_size = [display_width * scale, display_height * scale];
             // Calculate top-left origin in pixel space for centered canvas
             let display_origin = [
                 (width as i32 - display_size[0] as i32) / 2,
@@ -488,5 +517,67 @@ impl Renderer {
     pub fn update_display(&mut self, display: &DisplayBuffer) {
         self.display.buffer = *display;
         self.display.update_buffer(&self.queue);
+        self.set_display_mode(0, Self::DISPLAY_SIZE);
+    }
+
+    /// Updates the display from a CGB mode color buffer
+    pub fn update_color_display(&mut self, display: &ColorDisplayBuffer) {
+        self.write_color_texture(display, Self::DISPLAY_SIZE);
+        self.set_display_mode(1, Self::DISPLAY_SIZE);
+    }
+
+    /// Updates the display from a Super Game Boy picture, which includes the border
+    pub fn update_sgb_display(&mut self, frame: &SGBFrameBuffer) {
+        let size = [SGB_FRAME_WIDTH as u32, SGB_FRAME_HEIGHT as u32];
+        self.write_color_texture(frame, size);
+        self.set_display_mode(1, size);
+    }
+
+    /// Writes RGB555 pixels of given size to the top-left corner of the color display texture
+    fn write_color_texture(&mut self, pixels: &[u16], [width, height]: [u32; 2]) {
+        // Expand the 5-bit channels of each pixel to 8 bits
+        let expand = |channel: u16| {
+            let channel = (channel & 0x1F) as u8;
+            (channel << 3) | (channel >> 2)
+        };
+        let rgba: Vec<u8> = pixels
+            .iter()
+            .flat_map(|&color| [expand(color), expand(color >> 5), expand(color >> 10), 255])
+            .collect();
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.color_display_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Sets where the display is drawn from and its size, resizing the display if needed
+    fn set_display_mode(&mut self, color_mode: u32, size: [u32; 2]) {
+        if self.display_options.color_mode == color_mode
+            && self.display_options.display_size == size
+        {
+            return;
+        }
+        self.display_options.color_mode = color_mode;
+        if self.display_options.display_size != size {
+            self.display_options.display_size = size;
+            // Resizing recalculates the scale and updates the options
+            self.resize(self.config.width, self.config.height);
+        }
+        self.display_options.update_buffer(&self.queue);
     }
 }